@@ -0,0 +1,232 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::{fs::File, io::Write, process::exit};
+
+use clap::{ArgEnum, Parser};
+use cuesheet_rs::{CueSheet, CueTrack};
+use serde::Serialize;
+
+use crate::{
+    commands::cuesheet::cuesheet_fetch::CueFileFormatLocal,
+    config::{check_credential_exist, extend_env},
+    libs::{
+        spotify::{AudioAnalysis, Spotify, Track},
+        spotify_id::{self, SpotifyIdType},
+        util,
+    },
+};
+
+/// A cue sheet can't describe more tracks
+const CUE_MAX_TRACKS: usize = 99;
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+pub enum MarkerKind {
+    Sections,
+    Bars,
+    Beats,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+pub enum ExportFormat {
+    Cue,
+    Audacity,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct Marker {
+    label: String,
+    /// In seconds
+    start: f64,
+    /// In seconds
+    duration: f64,
+    confidence: f64,
+}
+
+/// Export the sections, bars or beats of a track audio analysis
+#[derive(Parser)]
+pub struct Analysis {
+    /// Search by track id, url or uri
+    #[clap(long)]
+    id: bool,
+    /// Markers to export
+    #[clap(short, long, arg_enum, default_value = "sections")]
+    markers: MarkerKind,
+    /// Export format
+    #[clap(short, long, arg_enum, default_value = "cue")]
+    export: ExportFormat,
+    /// Output file
+    #[clap(short, long, help = "Output file [stdout if not present]")]
+    output: Option<String>,
+    /// file that the cue sheet describes
+    #[clap(long, alias = "cfn", default_value = "")]
+    cue_file_name: String,
+    /// Format of the file that the cue sheet describes
+    #[clap(long, arg_enum, default_value = "mp3")]
+    cue_format: CueFileFormatLocal,
+    /// Track name or id
+    track: String,
+}
+
+impl MarkerKind {
+    fn markers(&self, analysis: &AudioAnalysis) -> Vec<Marker> {
+        match self {
+            MarkerKind::Sections => analysis
+                .sections
+                .iter()
+                .enumerate()
+                .map(|(index, section)| {
+                    let mut label = format!("Section {} - {:.0} BPM", index + 1, section.tempo);
+                    if let Some(key) = section.key_name() {
+                        label.push_str(format!(" - {}", key).as_str())
+                    }
+                    Marker {
+                        label,
+                        start: section.start,
+                        duration: section.duration,
+                        confidence: section.confidence,
+                    }
+                })
+                .collect(),
+            MarkerKind::Bars | MarkerKind::Beats => {
+                let (name, intervals) = match self {
+                    MarkerKind::Bars => ("Bar", &analysis.bars),
+                    _ => ("Beat", &analysis.beats),
+                };
+                intervals
+                    .iter()
+                    .enumerate()
+                    .map(|(index, interval)| Marker {
+                        label: format!("{} {}", name, index + 1),
+                        start: interval.start,
+                        duration: interval.duration,
+                        confidence: interval.confidence,
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Analysis {
+    pub async fn run(self, no_cache: bool) {
+        let Analysis {
+            id,
+            markers,
+            export,
+            output,
+            cue_file_name,
+            cue_format,
+            track,
+        } = self;
+        let () = extend_env();
+        let () = match check_credential_exist() {
+            true => (),
+            false => return,
+        };
+        let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
+        let track_id = match id {
+            true => spotify_id::parse(&track, SpotifyIdType::Track).unwrap_or_else(|e| e.exit()),
+            false => spotify
+                .get_track_id(&track)
+                .await
+                .unwrap_or_else(|e| e.exit()),
+        };
+        let track = spotify.track(&track_id).await.unwrap_or_else(|e| e.exit());
+        let analysis = spotify
+            .audio_analysis(&track_id)
+            .await
+            .unwrap_or_else(|e| e.exit());
+        let markers = markers.markers(&analysis);
+        let content = match export {
+            ExportFormat::Cue => Self::cuesheet(&track, &markers, cue_file_name, cue_format),
+            ExportFormat::Audacity => Ok(Self::audacity_labels(&markers)),
+            ExportFormat::Json => serde_json::to_string_pretty(&markers).map_err(|e| e.to_string()),
+        };
+        let content = content.unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1)
+        });
+        match output {
+            Some(output) => {
+                let result =
+                    File::create(&output).and_then(|mut file| file.write_all(content.as_bytes()));
+                if let Err(e) = result {
+                    eprintln!("Unable to write {} : {}", output, e);
+                    exit(1)
+                }
+            }
+            None => println!("{}", content),
+        }
+    }
+
+    fn cuesheet(
+        track: &Track,
+        markers: &[Marker],
+        cue_file_name: String,
+        cue_format: CueFileFormatLocal,
+    ) -> Result<String, String> {
+        if markers.len() > CUE_MAX_TRACKS {
+            return Err(format!(
+                "{} markers but a cue sheet is limited to {} tracks, export to audacity or json instead",
+                markers.len(),
+                CUE_MAX_TRACKS
+            ));
+        }
+        let performer = track
+            .artists
+            .iter()
+            .map(|artist| artist.name.clone())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let mut cue_sheet = CueSheet::new(cue_file_name.as_str(), cue_format.to_cuefileformat());
+        cue_sheet.add_title(track.name.as_str());
+        if !performer.is_empty() {
+            cue_sheet.add_performer(performer.as_str());
+        }
+        markers.iter().enumerate().for_each(|(index, marker)| {
+            let mut cuetrack = CueTrack::new(index as u32 + 1, cuesheet_rs::CueTrackMode::AUDIO);
+            cuetrack.add_title(marker.label.as_str());
+            if !performer.is_empty() {
+                let _ = cuetrack.add_performer(performer.as_str());
+            }
+            cuetrack.add_index(
+                1,
+                util::convert_mille_to_duration((marker.start * 1000.0).round() as u64),
+            );
+            cue_sheet.add_track(cuetrack);
+        });
+        Ok(cue_sheet.repr(true))
+    }
+
+    /// Audacity label track: start, end and label separated by tabs
+    fn audacity_labels(markers: &[Marker]) -> String {
+        markers
+            .iter()
+            .map(|marker| {
+                format!(
+                    "{:.6}\t{:.6}\t{}",
+                    marker.start,
+                    marker.start + marker.duration,
+                    marker.label
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
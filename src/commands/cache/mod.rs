@@ -0,0 +1,127 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use clap::{Parser, Subcommand};
+
+use serde::Serialize;
+
+use crate::libs::{
+    cache::ResponseCache,
+    output::{self, record, OutputFormat},
+    spotify::SpotifyRessourceType,
+};
+
+/// Remove the cached api responses
+#[derive(Parser)]
+pub struct CacheClear {
+    /// Only remove the responses of these ressources
+    #[clap(short, long, arg_enum)]
+    ressources: Vec<SpotifyRessourceType>,
+}
+
+/// Show the cached api responses
+#[derive(Parser)]
+pub struct CacheStats {}
+
+#[derive(Subcommand)]
+/// Manage the api responses cache
+pub enum CacheSubcommand {
+    Clear(CacheClear),
+    Stats(CacheStats),
+}
+
+impl CacheSubcommand {
+    pub fn run(self, output_format: OutputFormat) {
+        match self {
+            CacheSubcommand::Clear(clear) => clear.run(),
+            CacheSubcommand::Stats(stats) => stats.run(output_format),
+        }
+    }
+}
+
+impl CacheClear {
+    pub fn run(self) {
+        let r_types = match self.ressources.is_empty() {
+            true => SpotifyRessourceType::ALL.to_vec(),
+            false => self.ressources,
+        };
+        let removed = ResponseCache::clear(&r_types);
+        println!("{} cached responses removed", removed)
+    }
+}
+
+#[derive(Serialize)]
+struct CacheStatsRecord {
+    ressource: &'static str,
+    entries: usize,
+    expired: usize,
+    size: u64,
+}
+
+record! {
+    CacheStatsRecord, |record| {
+        "ressource" => record.ressource.to_string(),
+        "entries" => record.entries.to_string(),
+        "expired" => record.expired.to_string(),
+        "size" => record.size.to_string(),
+    }
+}
+
+impl CacheStats {
+    pub fn run(self, output_format: OutputFormat) {
+        if !output_format.is_text() {
+            let records = SpotifyRessourceType::ALL
+                .iter()
+                .map(|r_type| {
+                    let stats = ResponseCache::stats(r_type);
+                    CacheStatsRecord {
+                        ressource: r_type.cache_name(),
+                        entries: stats.entries,
+                        expired: stats.expired,
+                        size: stats.size,
+                    }
+                })
+                .collect::<Vec<_>>();
+            return output::print_records(output_format, &records);
+        }
+        let mut total_entries = 0;
+        let mut total_size = 0;
+        println!(
+            "{:<20}{:>10}{:>10}{:>12}",
+            "Ressource", "Entries", "Expired", "Size (kB)"
+        );
+        for r_type in SpotifyRessourceType::ALL.iter() {
+            let stats = ResponseCache::stats(r_type);
+            total_entries += stats.entries;
+            total_size += stats.size;
+            println!(
+                "{:<20}{:>10}{:>10}{:>12}",
+                r_type.cache_name(),
+                stats.entries,
+                stats.expired,
+                stats.size / 1024
+            );
+        }
+        println!(
+            "{:<20}{:>10}{:>10}{:>12}",
+            "total",
+            total_entries,
+            "",
+            total_size / 1024
+        );
+    }
+}
@@ -25,11 +25,33 @@ use std::{
 
 use clap::Parser;
 
-const DEFAULT_AUDIO_FILE_EXTENSION: &[&'static str] =
-    &["mp3", "aiff", "flac", "wav", "alac", "ogg"];
+/// Extensions of the containers that koto edits
+const DEFAULT_AUDIO_FILE_EXTENSION: &[&str] = &[
+    "mp3", "flac", "ogg", "oga", "opus", "m4a", "mp4", "aac", "alac", "wav", "wave", "aif", "aiff",
+    "aifc",
+];
+
+pub(crate) fn default_extensions() -> HashSet<String> {
+    DEFAULT_AUDIO_FILE_EXTENSION
+        .iter()
+        .map(|extension| extension.to_lowercase())
+        .collect()
+}
+
+/// The default extensions with the included ones and without the excluded ones
+pub(crate) fn extensions(include: &[String], exclude: &[String]) -> HashSet<String> {
+    let mut extensions = default_extensions();
+    let _ = include.iter().for_each(|extension| {
+        let _ = extensions.insert(extension.to_lowercase());
+    });
+    let _ = exclude.iter().for_each(|extension| {
+        let _ = extensions.remove(&extension.to_lowercase());
+    });
+    extensions
+}
 
-struct M3UPlaylist {
-    items: Vec<String>,
+pub(crate) struct M3UPlaylist {
+    pub(crate) items: Vec<String>,
 }
 
 impl Display for M3UPlaylist {
@@ -43,11 +65,11 @@ impl Display for M3UPlaylist {
 }
 
 impl M3UPlaylist {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self { items: vec![] }
     }
 
-    fn _append_path<T: AsRef<Path>>(&mut self, path: T) {
+    pub(crate) fn append_path<T: AsRef<Path>>(&mut self, path: T) {
         self.items.push(
             path.as_ref()
                 .canonicalize()
@@ -86,17 +108,26 @@ impl M3UPlaylist {
         })
     }
 
-    fn append_sub_dir(&mut self, extensions: &HashSet<String>, path: &PathBuf) {
+    pub(crate) fn append_sub_dir(&mut self, extensions: &HashSet<String>, path: &PathBuf) {
         self.extract_audio_files(extensions, path);
         self.items.sort()
     }
+
+    pub(crate) fn save<P: AsRef<Path>>(&self, output: P) -> std::io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(output)?;
+        file.write_all(self.to_string().as_bytes())
+    }
 }
 
 /// Create M3U playlist
 #[derive(Parser)]
 pub struct CreateM3U {
     /// Include files
-    /// By default, matched files are [mp3, aiff, flac, wav, alac, ogg]
+    /// By default, matched files are [mp3, flac, ogg, oga, opus, m4a, mp4, aac, alac, wav, wave, aif, aiff, aifc]
     #[clap(short, long)]
     include_extension: Vec<String>,
 
@@ -123,17 +154,7 @@ impl CreateM3U {
             output,
             directories,
         } = self;
-        let mut extensions = HashSet::new();
-        let _ = DEFAULT_AUDIO_FILE_EXTENSION.iter().for_each(|extension| {
-            let _ = extensions.insert(extension.to_string().to_lowercase());
-        });
-        let _ = include_extension.iter().for_each(|extension| {
-            let _ = extensions.insert(extension.to_owned().to_lowercase());
-        });
-
-        let _ = exclude_extension.iter().for_each(|extension| {
-            let _ = extensions.remove(&extension.to_lowercase());
-        });
+        let extensions = extensions(&include_extension, &exclude_extension);
 
         let mut m3u_playlist = M3UPlaylist::new();
         for raw_path in directories.iter() {
@@ -148,16 +169,9 @@ impl CreateM3U {
 
         match output {
             None => println!("{}", m3u_playlist),
-            Some(output) => {
-                let mut file = OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .truncate(true)
-                    .open(output)
-                    .unwrap_or_else(|error| panic!("{}", error));
-                let _ = file.write_all(m3u_playlist.to_string().as_bytes());
-                ()
-            }
+            Some(output) => m3u_playlist
+                .save(output)
+                .unwrap_or_else(|error| panic!("{}", error)),
         }
     }
 }
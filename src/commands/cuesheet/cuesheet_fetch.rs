@@ -19,7 +19,10 @@ use std::process::exit;
 
 use crate::{
     config::{check_credential_exist, extend_env},
+    libs::output::OutputFormat,
+    libs::picker,
     libs::spotify::{Spotify, SpotifySearchType},
+    libs::spotify_id::{self, SpotifyIdType},
     libs::util,
 };
 use clap::{ArgEnum, ArgGroup, Parser};
@@ -42,7 +45,7 @@ pub struct CueSheetFetch {
     /// Album name
     #[clap(long, alias = "al")]
     album: Option<String>,
-    /// Album spotify Id
+    /// Album spotify id, url or uri
     #[clap(long, alias = "id")]
     album_id: Option<String>,
     /// Output file
@@ -61,6 +64,12 @@ pub struct CueSheetFetch {
     /// output path where fetched album illustration will be created
     #[clap(short, long)]
     image: Option<String>,
+    /// Take the first search result instead of choosing it
+    #[clap(long)]
+    first: bool,
+    /// Show the album covers of the search results
+    #[clap(short, long)]
+    graphic: bool,
 }
 
 #[derive(Debug, Clone, Copy, ArgEnum)]
@@ -85,7 +94,7 @@ impl CueFileFormatLocal {
 }
 
 impl CueSheetFetch {
-    pub async fn run(self) {
+    pub async fn run(self, no_cache: bool, output_format: OutputFormat) {
         let CueSheetFetch {
             artist,
             album,
@@ -95,76 +104,40 @@ impl CueSheetFetch {
             format,
             total_duration,
             image,
+            first,
+            graphic,
         } = self;
         let () = extend_env();
         let () = match check_credential_exist() {
             true => (),
             false => return,
         };
-        let spotify = Spotify::init().await;
+        let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
 
         let album_id = if let Some(id) = album_id {
-            id
+            spotify_id::parse(&id, SpotifyIdType::Album).unwrap_or_else(|e| e.exit())
         } else {
             let artist = artist.unwrap();
             let album = album.unwrap();
-            match spotify
-                .search(
-                    format!("{} {}", album, artist).as_str(),
-                    vec![SpotifySearchType::Album],
-                    None,
-                    Some(1),
-                    None,
-                    None,
-                )
-                .await
-            {
-                None => {
-                    println!("No Resultat");
-                    exit(1)
-                }
-                Some(result) => {
-                    let data = result
-                        .get(&crate::libs::spotify::SpotifySearchKey::Albums)
-                        .unwrap_or_else(|| {
-                            println!("Unable to fetch the artist");
-                            exit(1)
-                        });
-                    data.items.iter().filter_map(|ssri| match ssri {
-                        crate::libs::spotify::SpotifySearchResultItem::Album {
-                            album_type: _,
-                            artists: _,
-                            external_urls: _,
-                            available_markets: _,
-                            href: _,
-                            id,
-                            images: _,
-                            name: _,
-                            release_date: _,
-                            release_date_precision: _,
-                            total_tracks: _,
-                            a_type: _,
-                            uri: _,
-                        } => Some(id.clone()),
-                        _ => None,
-                    })
-                }
-                .collect::<Vec<String>>()
-                .first()
-                .unwrap_or_else(|| {
-                    println!("Unable to get the artist Id");
-                    exit(1)
-                })
-                .clone(),
-            }
+            picker::search_and_pick(
+                &spotify,
+                format!("{} {}", album, artist).as_str(),
+                SpotifySearchType::Album,
+                first,
+                graphic,
+            )
+            .await
+            .unwrap_or_else(|e| e.exit())
         };
-        if let Err(e) = util::cuesheet_from_album_id(
+        let album = spotify.album(album_id).await.unwrap_or_else(|e| e.exit());
+        if let Err(e) = util::cuesheet_from_album(
             cue_file_name,
             format.to_cuefileformat(),
             output,
-            album_id.as_str(),
+            &album,
             total_duration,
             image,
+            output_format,
         )
         .await
         {
@@ -17,6 +17,8 @@
 
 use clap::Subcommand;
 
+use crate::libs::output::OutputFormat;
+
 pub mod cuesheet_fetch;
 pub mod cuesheet_make;
 
@@ -28,9 +30,9 @@ pub enum CueSheetSubcommand {
 }
 
 impl CueSheetSubcommand {
-    pub async fn run(self) {
+    pub async fn run(self, no_cache: bool, output_format: OutputFormat) {
         match self {
-            CueSheetSubcommand::Fetch(fetch) => fetch.run().await,
+            CueSheetSubcommand::Fetch(fetch) => fetch.run(no_cache, output_format).await,
             CueSheetSubcommand::Make(make) => make.run(),
         }
     }
@@ -15,22 +15,69 @@
 //                                                                                            //
 // /////////////////////////////////////////////////////////////////////////////////////////////
 
-use std::{path::PathBuf, process::exit, str::FromStr};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    process::exit,
+    str::FromStr,
+};
 
 use clap::{ArgEnum, Parser};
 use tag_edit::{FlacTag, PictureFormat, ID3TAG};
 
+use crate::{
+    commands::create_m3u,
+    config::{check_credential_exist, extend_env},
+    libs::{
+        glob,
+        spotify::{Spotify, Track},
+        spotify_id::{self, SpotifyIdType},
+        tags::{self, container::Container, iff::IffFile, mp4::Mp4File, ogg::OggFile},
+    },
+};
+
 #[derive(Clone, Copy, Debug, ArgEnum)]
 pub enum FileType {
     Mp3,
     Flac,
+    /// Ogg vorbis or opus
+    Ogg,
+    /// Mp4 audio (m4a, alac)
+    Mp4,
+    Wav,
+    Aiff,
+}
+
+impl FileType {
+    fn container(&self) -> Container {
+        match self {
+            FileType::Mp3 => Container::Mp3,
+            FileType::Flac => Container::Flac,
+            FileType::Ogg => Container::Ogg,
+            FileType::Mp4 => Container::Mp4,
+            FileType::Wav => Container::Wav,
+            FileType::Aiff => Container::Aiff,
+        }
+    }
+
+    fn from_container(container: Container) -> Self {
+        match container {
+            Container::Mp3 => Self::Mp3,
+            Container::Flac => Self::Flac,
+            Container::Ogg => Self::Ogg,
+            Container::Mp4 => Self::Mp4,
+            Container::Wav => Self::Wav,
+            Container::Aiff => Self::Aiff,
+        }
+    }
 }
 
-/// Edit mp3 and flac file
-#[derive(Parser)]
+/// Edit mp3, flac, ogg (vorbis and opus), mp4, wav and aiff file
+#[derive(Parser, Clone)]
 pub struct Edit {
+    /// Type of the file, detected from its content if not given
     #[clap(long = "type", arg_enum)]
-    file_type: FileType,
+    file_type: Option<FileType>,
     /// Set the music title
     #[clap(short, long)]
     title: Option<String>,
@@ -53,25 +100,254 @@ pub struct Edit {
     #[clap(long)]
     /// Set track position
     track_position: Option<u16>,
+    /// Set disc position (mp4, wav and aiff files)
+    #[clap(long)]
+    disc_position: Option<u16>,
+    /// Set the initial key (ex: Am, F#)
+    #[clap(long)]
+    key: Option<String>,
+    /// Fill the unset bpm and initial key with the audio features of a spotify track (id, url or uri)
+    #[clap(long, value_name = "TRACK_ID")]
+    features_from: Option<String>,
     /// Add images
     #[clap(long)]
     images: Option<Vec<String>>,
     /// Output the
     #[clap(short)]
     output: Option<String>,
-    /// Audio file
-    file: String,
+    /// Include files while looking into directories
+    /// By default, matched files are the ones of create-m3u (mp3, flac, ogg, opus, m4a, wav, aiff...)
+    #[clap(short, long)]
+    include_extension: Vec<String>,
+    /// Exclude files from being matched while looking into directories
+    #[clap(short, long)]
+    exclude_extension: Vec<String>,
+    /// Keep editing the next files when one fails, the failures are reported at the end
+    #[clap(long)]
+    continue_on_error: bool,
+    /// Audio files, directories (looked into recursively) or glob patterns
+    #[clap(required = true)]
+    files: Vec<String>,
 }
 
 impl Edit {
-    pub fn run(self) {
-        match &self.file_type {
-            FileType::Mp3 => self.run_mp3(),
-            FileType::Flac => self.run_flac(),
+    /// Edit setting the title, artists, album, year and position of a spotify track.
+    /// The bpm and the key are also set with `with_features`
+    pub(crate) fn from_track(
+        file_type: Option<FileType>,
+        file: String,
+        track: &Track,
+        with_features: bool,
+    ) -> Self {
+        let artist_album = track
+            .album
+            .artists
+            .iter()
+            .map(|artist| artist.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Self {
+            file_type,
+            title: Some(track.name.clone()),
+            artist: Some(track.artists_name()),
+            album: Some(track.album.name.clone()),
+            artist_album: Some(artist_album),
+            year: i16::try_from(track.album.release_date.year()).ok(),
+            bpm: None,
+            track_position: Some(track.track_number),
+            disc_position: None,
+            key: None,
+            features_from: with_features.then(|| track.id.clone()),
+            images: None,
+            output: None,
+            include_extension: vec![],
+            exclude_extension: vec![],
+            continue_on_error: false,
+            files: vec![file],
+        }
+    }
+
+    /// The given type if it doesn't contradict the content of the file, or the detected one
+    fn resolve_file_type(&self, file: &str) -> Result<FileType, String> {
+        let sniffed =
+            Container::sniff(file).map_err(|e| format!("Cannot read {} : {}", file, e))?;
+        match (self.file_type, sniffed) {
+            (Some(file_type), Some(container)) if file_type.container() != container => {
+                Err(format!(
+                    "{} is a {} file but --type {} was given",
+                    file,
+                    container,
+                    file_type.container()
+                ))
+            }
+            (Some(file_type), _) => Ok(file_type),
+            (None, sniffed) => sniffed
+                .or_else(|| Container::from_extension(file))
+                .map(FileType::from_container)
+                .ok_or_else(|| format!("{} : unknown audio container, set it with --type", file)),
+        }
+    }
+
+    /// Whether the extension is one of the matched ones, see [create_m3u::extensions]
+    fn has_audio_extension(extensions: &HashSet<String>, path: &Path) -> bool {
+        path.extension()
+            .map(|extension| extensions.contains(&extension.to_string_lossy().to_lowercase()))
+            .unwrap_or(false)
+    }
+
+    /// Audio files of the directory and its sub directories, the symbolic links to directories aren't followed
+    fn directory_files(
+        extensions: &HashSet<String>,
+        dir: &Path,
+        files: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let entries = std::fs::read_dir(dir)
+            .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Cannot read {} : {}", dir.display(), e))?;
+        let mut paths = entries
+            .iter()
+            .map(|entry| {
+                let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+                (entry.path(), is_dir)
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        for (path, is_dir) in paths {
+            if is_dir {
+                let () = Self::directory_files(extensions, &path, files)?;
+            } else if path.is_file() && Self::has_audio_extension(extensions, &path) {
+                files.push(path.to_string_lossy().to_string())
+            }
+        }
+        Ok(())
+    }
+
+    /// Audio files to edit, the directories and the glob patterns are filtered by extension
+    fn audio_files(&self) -> Result<Vec<String>, String> {
+        let extensions = create_m3u::extensions(&self.include_extension, &self.exclude_extension);
+        let mut files = vec![];
+        for raw_path in self.files.iter() {
+            if !glob::is_pattern(raw_path) {
+                let path = PathBuf::from(raw_path);
+                match path.is_dir() {
+                    true => Self::directory_files(&extensions, &path, &mut files)?,
+                    false if path.is_file() => files.push(raw_path.clone()),
+                    false => return Err(format!("{} : no such file or directory", raw_path)),
+                }
+                continue;
+            }
+            let paths = glob::expand(raw_path);
+            if paths.is_empty() {
+                return Err(format!("{} : no matching path", raw_path));
+            }
+            for path in paths {
+                if path.is_dir() {
+                    let () = Self::directory_files(&extensions, &path, &mut files)?;
+                } else if Self::has_audio_extension(&extensions, &path) {
+                    files.push(path.to_string_lossy().to_string())
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        files.retain(|file| {
+            let path = Path::new(file);
+            seen.insert(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()))
+        });
+        Ok(files)
+    }
+
+    pub async fn run(mut self, no_cache: bool) {
+        let files = self.audio_files().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1)
+        });
+        if files.is_empty() {
+            eprintln!("No matched files");
+            exit(1)
+        }
+        if files.len() > 1 {
+            let per_file_options = [
+                ("--title", self.title.is_some()),
+                ("--track-position", self.track_position.is_some()),
+                ("--bpm", self.bpm.is_some()),
+                ("--key", self.key.is_some()),
+                ("--features-from", self.features_from.is_some()),
+                ("-o", self.output.is_some()),
+            ];
+            if let Some((option, _)) = per_file_options.iter().find(|(_, set)| *set) {
+                eprintln!("{} can only be given when editing a single file", option);
+                exit(1)
+            }
+        }
+        if let Some(track_id) = &self.features_from {
+            let () = extend_env();
+            let () = match check_credential_exist() {
+                true => (),
+                false => return,
+            };
+            let track_id =
+                spotify_id::parse(track_id, SpotifyIdType::Track).unwrap_or_else(|e| e.exit());
+            let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
+            let features = spotify
+                .audio_features(&track_id)
+                .await
+                .unwrap_or_else(|e| e.exit());
+            self.bpm = self.bpm.or_else(|| Some(features.bpm()));
+            self.key = self.key.take().or_else(|| features.initial_key());
+        }
+        let mut failures = vec![];
+        for file in files {
+            if let Err(e) = self.edit_file(file.clone()) {
+                match self.continue_on_error {
+                    true => failures.push((file, e)),
+                    false => {
+                        eprintln!("{}", e);
+                        exit(1)
+                    }
+                }
+            }
+        }
+        if !failures.is_empty() {
+            eprintln!("{} file(s) failed:", failures.len());
+            for (file, e) in failures {
+                eprintln!("    {} : {}", file, e)
+            }
+            exit(1)
+        }
+    }
+
+    fn edit_file(&self, file: String) -> Result<(), String> {
+        let file_type = self.resolve_file_type(&file)?;
+        if self.disc_position.is_some()
+            && matches!(file_type, FileType::Mp3 | FileType::Flac | FileType::Ogg)
+        {
+            return Err(format!(
+                "{} : --disc-position is only supported for mp4, wav and aiff files",
+                file
+            ));
+        }
+        let path = self.output.clone().unwrap_or_else(|| file.clone());
+        let edit = self.clone();
+        let () = match file_type {
+            FileType::Mp3 => edit.run_mp3(file),
+            FileType::Flac => edit.run_flac(file),
+            FileType::Ogg => edit.run_ogg(file),
+            FileType::Mp4 => edit.run_mp4(file),
+            FileType::Wav | FileType::Aiff => edit.run_iff(file),
+        }?;
+        if let Some(key) = &self.key {
+            let result = match file_type {
+                FileType::Mp3 => tags::id3::set_text_frame(&path, "TKEY", key),
+                FileType::Flac => tags::flac::set_comment(&path, "INITIALKEY", key),
+                // Written with the other fields
+                FileType::Ogg | FileType::Mp4 | FileType::Wav | FileType::Aiff => Ok(()),
+            };
+            let () = result.map_err(|e| format!("Unable to write the initial key: {}", e))?;
         }
+        Ok(())
     }
 
-    fn run_mp3(self) {
+    fn run_mp3(self, file: String) -> Result<(), String> {
         let Edit {
             file_type: _,
             title,
@@ -81,16 +357,19 @@ impl Edit {
             year,
             bpm,
             track_position,
+            disc_position: _,
+            key: _,
+            features_from: _,
             images,
             output,
-            file,
+            include_extension: _,
+            exclude_extension: _,
+            continue_on_error: _,
+            files: _,
         } = self;
         let mut id3tag = match ID3TAG::from_path(file.as_str()) {
             Some(id3tag) => id3tag,
-            None => {
-                println!("File {} not found", &file);
-                return;
-            }
+            None => return Err(format!("Cannot read the id3 tag of {}", &file)),
         };
         if let Some(title) = title {
             id3tag.set_title(title.as_str())
@@ -133,27 +412,22 @@ impl Edit {
                         None,
                         None,
                     )
-                    .ok()
-                    .unwrap_or_else(|| {
-                        println!("Unable to add the picture");
-                        exit(1)
-                    });
+                    .map_err(|_| "Unable to add the picture".to_string())?;
             }
         }
         if let Some(output) = output {
-            let _ = id3tag.write_tag(output.as_str()).unwrap_or_else(|_| {
-                println!("Unable to write the file");
-                exit(1)
-            });
+            let _ = id3tag
+                .write_tag(output.as_str())
+                .map_err(|_| "Unable to write the file".to_string())?;
         } else {
-            let _ = id3tag.overwrite_tag().unwrap_or_else(|_| {
-                println!("Unable to write the file");
-                exit(1)
-            });
+            let _ = id3tag
+                .overwrite_tag()
+                .map_err(|_| "Unable to write the file".to_string())?;
         }
+        Ok(())
     }
 
-    fn run_flac(self) {
+    fn run_flac(self, file: String) -> Result<(), String> {
         let Edit {
             file_type: _,
             title,
@@ -163,16 +437,19 @@ impl Edit {
             year,
             bpm,
             track_position,
+            disc_position: _,
+            key: _,
+            features_from: _,
             images,
             output,
-            file,
+            include_extension: _,
+            exclude_extension: _,
+            continue_on_error: _,
+            files: _,
         } = self;
         let mut flac = match FlacTag::from_path(file.as_str()) {
             Some(tag) => tag,
-            None => {
-                println!("Cannot open {}", &file);
-                return;
-            }
+            None => return Err(format!("Cannot open {}", &file)),
         };
         if let Some(title) = title {
             flac.set_title(title.as_str())
@@ -220,24 +497,210 @@ impl Edit {
                         16,
                         None,
                     )
-                    .ok()
-                    .unwrap_or_else(|| {
-                        println!("Unable to add the picture");
-                        exit(1)
-                    });
+                    .map_err(|_| "Unable to add the picture".to_string())?;
             }
         }
 
         if let Some(output) = output {
-            let _ = flac.write_flac(output.as_str()).unwrap_or_else(|_| {
-                println!("Unable to write the file");
-                exit(1)
-            });
+            let _ = flac
+                .write_flac(output.as_str())
+                .map_err(|_| "Unable to write the file".to_string())?;
         } else {
-            let _ = flac.overwrite_flac().unwrap_or_else(|_| {
-                println!("Unable to write the file");
-                exit(1)
-            });
+            let _ = flac
+                .overwrite_flac()
+                .map_err(|_| "Unable to write the file".to_string())?;
         }
+        Ok(())
+    }
+
+    fn run_ogg(self, file: String) -> Result<(), String> {
+        let Edit {
+            file_type: _,
+            title,
+            artist,
+            album,
+            artist_album,
+            year,
+            bpm,
+            track_position,
+            disc_position: _,
+            key,
+            features_from: _,
+            images,
+            output,
+            include_extension: _,
+            exclude_extension: _,
+            continue_on_error: _,
+            files: _,
+        } = self;
+        let mut ogg = match OggFile::from_path(file.as_str()) {
+            Ok(ogg) => ogg,
+            Err(e) => return Err(format!("Cannot open {} : {}", &file, e)),
+        };
+        let mut comment = ogg
+            .vorbis_comment()
+            .map_err(|e| format!("Cannot read the comment of {} : {}", &file, e))?;
+        let fields = [
+            ("TITLE", title),
+            ("ARTIST", artist),
+            ("ALBUM", album),
+            ("ALBUMARTIST", artist_album),
+            ("DATE", year.map(|year| year.to_string())),
+            ("BPM", bpm.map(|bpm| bpm.to_string())),
+            ("TRACKNUMBER", track_position.map(|tp| tp.to_string())),
+            ("INITIALKEY", key),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                comment.set(key, value.as_str())
+            }
+        }
+        for image in images.unwrap_or_default() {
+            let () = comment
+                .add_picture(&image)
+                .map_err(|e| format!("Unable to add the picture {} : {}", image, e))?;
+        }
+        let result = ogg
+            .set_vorbis_comment(&comment)
+            .and_then(|()| ogg.write(output.as_ref().unwrap_or(&file)));
+        result.map_err(|e| format!("Unable to write the file : {}", e))
+    }
+
+    fn run_mp4(self, file: String) -> Result<(), String> {
+        let Edit {
+            file_type: _,
+            title,
+            artist,
+            album,
+            artist_album,
+            year,
+            bpm,
+            track_position,
+            disc_position,
+            key,
+            features_from: _,
+            images,
+            output,
+            include_extension: _,
+            exclude_extension: _,
+            continue_on_error: _,
+            files: _,
+        } = self;
+        let mut mp4 = match Mp4File::from_path(file.as_str()) {
+            Ok(mp4) => mp4,
+            Err(e) => return Err(format!("Cannot open {} : {}", &file, e)),
+        };
+        let texts = [
+            (b"\xA9nam", title),
+            (b"\xA9ART", artist),
+            (b"\xA9alb", album),
+            (b"aART", artist_album),
+            (b"\xA9day", year.map(|year| year.to_string())),
+        ];
+        let mut result = Ok(());
+        for (kind, value) in texts {
+            if let Some(value) = value {
+                result = result.and_then(|()| mp4.set_text(kind, value.as_str()))
+            }
+        }
+        if let Some(bpm) = bpm {
+            result = result.and_then(|()| mp4.set_bpm(bpm))
+        }
+        if let Some(tp) = track_position {
+            result = result.and_then(|()| mp4.set_track(tp))
+        }
+        if let Some(dp) = disc_position {
+            result = result.and_then(|()| mp4.set_disc(dp))
+        }
+        if let Some(key) = key {
+            result = result.and_then(|()| mp4.set_freeform("initialkey", key.as_str()))
+        }
+        let () = result.map_err(|e| format!("Cannot edit the atoms of {} : {}", &file, e))?;
+        for image in images.unwrap_or_default() {
+            let () = mp4
+                .add_cover(&image)
+                .map_err(|e| format!("Unable to add the picture {} : {}", image, e))?;
+        }
+        mp4.write(output.as_ref().unwrap_or(&file))
+            .map_err(|e| format!("Unable to write the file : {}", e))
+    }
+
+    /// Edit the ID3 chunk of a wav or aiff file along with its native text chunks
+    fn run_iff(self, file: String) -> Result<(), String> {
+        let Edit {
+            file_type: _,
+            title,
+            artist,
+            album,
+            artist_album,
+            year,
+            bpm,
+            track_position,
+            disc_position,
+            key,
+            features_from: _,
+            images,
+            output,
+            include_extension: _,
+            exclude_extension: _,
+            continue_on_error: _,
+            files: _,
+        } = self;
+        let mut iff = match IffFile::from_path(file.as_str()) {
+            Ok(iff) => iff,
+            Err(e) => return Err(format!("Cannot open {} : {}", &file, e)),
+        };
+        let mut id3tag = iff
+            .id3_tag()
+            .map_err(|e| format!("Cannot read the id3 chunk of {} : {}", &file, e))?;
+        let texts = [
+            ("TIT2", &title),
+            ("TPE1", &artist),
+            ("TALB", &album),
+            ("TPE2", &artist_album),
+            ("TKEY", &key),
+        ];
+        for (id, value) in texts {
+            if let Some(value) = value {
+                id3tag.set_text(id, value.as_str())
+            }
+        }
+        if let Some(year) = year {
+            id3tag.set_year(year)
+        }
+        let numbers = [
+            ("TBPM", bpm),
+            ("TRCK", track_position),
+            ("TPOS", disc_position),
+        ];
+        for (id, value) in numbers {
+            if let Some(value) = value {
+                id3tag.set_text(id, &value.to_string())
+            }
+        }
+        for image in images.unwrap_or_default() {
+            let () = id3tag
+                .add_picture(&image)
+                .map_err(|e| format!("Unable to add the picture {} : {}", image, e))?;
+        }
+        let () = iff.set_id3_tag(&id3tag);
+        let result = match iff.is_wav() {
+            true => [
+                (b"INAM", title),
+                (b"IART", artist),
+                (b"IPRD", album),
+                (b"ICRD", year.map(|year| year.to_string())),
+                (b"ITRK", track_position.map(|tp| tp.to_string())),
+            ]
+            .into_iter()
+            .filter_map(|(id, value)| Some((id, value?)))
+            .try_for_each(|(id, value)| iff.set_info(id, value.as_str())),
+            false => [(b"NAME", title), (b"AUTH", artist)]
+                .into_iter()
+                .filter_map(|(id, value)| Some((id, value?)))
+                .try_for_each(|(id, value)| iff.set_text_chunk(id, value.as_str())),
+        };
+        let result = result.and_then(|()| iff.write(output.as_ref().unwrap_or(&file)));
+        result.map_err(|e| format!("Unable to write the file : {}", e))
     }
 }
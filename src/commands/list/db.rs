@@ -15,13 +15,17 @@
 //                                                                                            //
 // /////////////////////////////////////////////////////////////////////////////////////////////
 
-use std::{fs::OpenOptions, time::Duration};
-
-use chrono::NaiveDate;
+use std::fs::OpenOptions;
 
 use crate::{
     config::{koto_base_dir, KOTO_DB_NAME},
-    libs::spotify::{self, SpotifySearchResultItem, SpotifySearchType},
+    libs::{
+        error::SpotifyResult,
+        output::{self, record, OutputFormat},
+        picker,
+        release_date::ReleaseDate,
+        spotify::{self, SpotifySearchType},
+    },
 };
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -29,46 +33,51 @@ pub struct Artist {
     pub(crate) artist_name: String,
     pub(crate) artist_spotify_id: String,
     pub(crate) last_album: String,
-    pub(crate) last_album_release_date: NaiveDate,
+    pub(crate) last_album_release_date: ReleaseDate,
     pub(crate) last_album_spotify_id: String,
     pub(crate) last_album_url: String,
 }
 
+record! {
+    Artist, |record| {
+        "artist_name" => record.artist_name.clone(),
+        "artist_spotify_id" => record.artist_spotify_id.clone(),
+        "last_album" => record.last_album.clone(),
+        "last_album_release_date" => record.last_album_release_date.to_string(),
+        "last_album_spotify_id" => record.last_album_spotify_id.clone(),
+        "last_album_url" => record.last_album_url.clone(),
+    }
+}
+
 impl Artist {
-    pub async fn from_id(spotify: &spotify::Spotify, id: &String) -> Option<Self> {
+    pub async fn from_id(spotify: &spotify::Spotify, id: &String) -> SpotifyResult<Self> {
         let artist = spotify.artist(id).await?;
         let lastest_album = spotify.artist_lastest_album(artist.id.as_str()).await?;
-        Some(Self {
+        Ok(Self {
             artist_name: artist.name,
             artist_spotify_id: artist.id,
             last_album: lastest_album.name,
             last_album_release_date: lastest_album.release_date,
             last_album_spotify_id: lastest_album.id,
-            last_album_url: lastest_album.external_urls.get("spotify")?.clone(),
+            last_album_url: lastest_album
+                .external_urls
+                .get("spotify")
+                .cloned()
+                .unwrap_or_default(),
         })
     }
 
-    pub async fn from_name(spotify: &spotify::Spotify, name: &String, id: bool) -> Option<Self> {
+    pub async fn from_name(
+        spotify: &spotify::Spotify,
+        name: &String,
+        id: bool,
+        first: bool,
+    ) -> SpotifyResult<Self> {
         let id = match id {
             true => name.clone(),
             false => {
-                let map = spotify
-                    .search(
-                        name.as_str(),
-                        vec![SpotifySearchType::Artist],
-                        None,
-                        Some(1),
-                        Some(0),
-                        None,
-                    )
-                    .await?;
-                let artist = map.get(&crate::libs::spotify::SpotifySearchKey::Artists)?;
-                let items = &artist.items;
-                let artist_id = items.iter().find_map(|rssri| match rssri {
-                    SpotifySearchResultItem::Artist { id, .. } => Some(id.clone()),
-                    _ => None,
-                })?;
-                artist_id.clone()
+                picker::search_and_pick(spotify, name, SpotifySearchType::Artist, first, false)
+                    .await?
             }
         };
         Self::from_id(spotify, &id).await
@@ -105,25 +114,25 @@ impl Artist {
         s
     }
 
-    pub async fn update(&mut self, spotify: &spotify::Spotify) -> bool {
-        let lastest = match spotify
+    pub async fn update(&mut self, spotify: &spotify::Spotify) -> SpotifyResult<bool> {
+        let lastest = spotify
             .artist_lastest_album(self.artist_spotify_id.as_str())
-            .await
-        {
-            Some(s) => s,
-            None => return false,
-        };
+            .await?;
         let should_update = &lastest.release_date > &self.last_album_release_date;
         let () = match should_update {
             true => {
                 self.last_album = lastest.name;
                 self.last_album_spotify_id = lastest.id;
-                self.last_album_release_date = lastest.release_date.clone();
-                self.last_album_url = lastest.external_urls.get("spotify").unwrap().clone();
+                self.last_album_release_date = lastest.release_date;
+                self.last_album_url = lastest
+                    .external_urls
+                    .get("spotify")
+                    .cloned()
+                    .unwrap_or_default();
             }
             false => (),
         };
-        should_update
+        Ok(should_update)
     }
 }
 
@@ -141,8 +150,16 @@ impl Default for Artists {
 }
 
 impl Artists {
-    pub async fn update(&mut self, id: bool, artist: &Option<String>) {
-        let spotify = spotify::Spotify::init().await;
+    /// Update the artists lastest album.
+    /// An artist failing to update doesn't stop the others, the last error is returned
+    pub async fn update(
+        &mut self,
+        spotify: &spotify::Spotify,
+        id: bool,
+        artist: &Option<String>,
+        output_format: OutputFormat,
+    ) -> SpotifyResult<()> {
+        let mut error = None;
         let mut artists = match artist {
             Some(name) => self
                 .artists
@@ -155,24 +172,46 @@ impl Artists {
                 .unwrap_or_default(),
             None => self.artists.iter_mut().collect(),
         };
-        println!();
-        println!("------------------------------");
-        println!("-------- New realease --------");
-        println!("------------------------------");
-        println!("");
+        if output_format.is_text() {
+            eprintln!();
+            eprintln!("------------------------------");
+            eprintln!("-------- New realease --------");
+            eprintln!("------------------------------");
+            eprintln!();
+        }
         let () = for artist in artists.iter_mut() {
-            let () = tokio::time::sleep(Duration::from_millis(300)).await;
-            match artist.update(&spotify).await {
-                false => (),
-                true => {
+            match artist.update(spotify).await {
+                Ok(false) => (),
+                Ok(true) => {
                     println!("\n");
                     println!("{}", artist.default_format());
                     (0..3)
                         .into_iter()
                         .for_each(|_| println!("------------------"));
                 }
+                Err(e) => {
+                    eprintln!("Unable to update {} : {}", artist.artist_name, e);
+                    error = Some(e);
+                }
             }
         };
+        match error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    pub fn artist_ids(&self) -> Vec<String> {
+        self.artists
+            .iter()
+            .map(|artist| artist.artist_spotify_id.clone())
+            .collect()
+    }
+
+    pub fn contains(&self, artist_id: &str) -> bool {
+        self.artists
+            .iter()
+            .any(|artist| artist.artist_spotify_id == artist_id)
     }
 
     pub fn add(&mut self, artist: Artist) {
@@ -186,7 +225,10 @@ impl Artists {
         })
     }
 
-    pub fn show(&self, full: bool) {
+    pub fn show(&self, full: bool, output_format: OutputFormat) {
+        if !output_format.is_text() {
+            return output::print_records(output_format, &self.artists);
+        }
         self.artists.iter().for_each(|artist| match full {
             true => println!("{}", artist.default_format()),
             false => println!("{}", artist.full_format()),
@@ -198,7 +240,7 @@ impl Artists {
         let path = match koto_dir.find_data_file(KOTO_DB_NAME) {
             Some(some) => some,
             None => {
-                println!("Error file not exist");
+                eprintln!("Error file not exist");
                 return;
             }
         };
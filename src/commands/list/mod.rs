@@ -21,7 +21,11 @@ use clap::{ArgGroup, Parser};
 
 use crate::{
     config::{check_credential_exist, extend_env},
-    libs::spotify::Spotify,
+    libs::{
+        output::OutputFormat,
+        spotify::Spotify,
+        spotify_id::{self, SpotifyIdType},
+    },
 };
 
 use self::db::{Artist, Artists};
@@ -49,13 +53,16 @@ pub struct List {
     /// Display all the artist information
     #[clap(short, long)]
     full: bool,
-    /// Filter with selected spotify id
+    /// Filter with selected spotify id, url or uri
     #[clap(short, long)]
     id: bool,
+    /// Take the first search result instead of choosing it
+    #[clap(long)]
+    first: bool,
 }
 
 impl List {
-    pub async fn run(self) {
+    pub async fn run(self, no_cache: bool, output_format: OutputFormat) {
         let () = extend_env();
         let () = match check_credential_exist() {
             true => (),
@@ -64,66 +71,82 @@ impl List {
         match (&self.add, &self.delete) {
             (Some(_), Some(_)) => unreachable!("Are mutual excluded"),
             (None, Some(name)) => self.run_delete(name).await,
-            (Some(name), None) => self.run_add(name).await,
+            (Some(name), None) => self.run_add(name, no_cache).await,
             (None, None) => match &self.update {
-                None => self.run_show(),
-                Some(artist_opt) => self.run_update(artist_opt).await,
+                None => self.run_show(output_format),
+                Some(artist_opt) => self.run_update(artist_opt, no_cache, output_format).await,
             },
         }
     }
 
-    async fn run_update(&self, artist_opt: &Option<String>) {
+    /// The artist name, or the artist id extracted from an id, url or uri with --id
+    fn artist_key(&self, name: &str) -> String {
+        match self.id {
+            true => spotify_id::parse(name, SpotifyIdType::Artist).unwrap_or_else(|e| e.exit()),
+            false => name.to_string(),
+        }
+    }
+
+    async fn run_update(
+        &self,
+        artist_opt: &Option<String>,
+        no_cache: bool,
+        output_format: OutputFormat,
+    ) {
         let mut db = match Artists::deserialize() {
             Some(db) => db,
             None => {
-                println!("Cannot deserialize");
+                eprintln!("Cannot deserialize");
                 return;
             }
         };
-        db.update(self.id, artist_opt).await;
+        let artist_opt = artist_opt.as_ref().map(|name| self.artist_key(name));
+        let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
+        let result = db
+            .update(&spotify, self.id, &artist_opt, output_format)
+            .await;
         db.save();
+        if let Err(e) = result {
+            e.exit()
+        }
     }
 
-    async fn run_add(&self, name: &String) {
+    async fn run_add(&self, name: &str, no_cache: bool) {
         let mut db = match Artists::deserialize() {
             Some(db) => db,
             None => {
-                println!("Cannot deserialize");
-                return;
-            }
-        };
-        let spotify = Spotify::init().await;
-        let artist = match Artist::from_name(&spotify, name, self.id).await {
-            Some(a) => a,
-            None => {
-                println!("Artist not found");
+                eprintln!("Cannot deserialize");
                 return;
             }
         };
+        let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
+        let artist = Artist::from_name(&spotify, &self.artist_key(name), self.id, self.first)
+            .await
+            .unwrap_or_else(|e| e.exit());
         db.add(artist);
         db.save();
     }
 
-    async fn run_delete(&self, name: &String) {
+    async fn run_delete(&self, name: &str) {
         let mut db = match Artists::deserialize() {
             Some(db) => db,
             None => {
-                println!("Cannot deserialize");
+                eprintln!("Cannot deserialize");
                 return;
             }
         };
-        db.delete(self.id, name);
+        db.delete(self.id, &self.artist_key(name));
         db.save();
     }
 
-    fn run_show(&self) {
+    fn run_show(&self, output_format: OutputFormat) {
         let db = match Artists::deserialize() {
             Some(db) => db,
             None => {
-                println!("Cannot deserialize");
+                eprintln!("Cannot deserialize");
                 return;
             }
         };
-        db.show(self.full)
+        db.show(self.full, output_format)
     }
 }
@@ -0,0 +1,59 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::libs::mock::MockServer;
+
+/// Serve json fixtures in place of the spotify api
+#[derive(Parser)]
+pub struct MockServerCommand {
+    /// Fixtures directory
+    #[clap(long, default_value = "fixtures")]
+    fixtures: PathBuf,
+    /// Listening address
+    #[clap(long, default_value = "127.0.0.1:0")]
+    address: String,
+}
+
+impl MockServerCommand {
+    pub async fn run(self) {
+        let Self { fixtures, address } = self;
+        let server = match MockServer::bind(fixtures, &address).await {
+            Ok(server) => server,
+            Err(e) => {
+                eprintln!("Error {}", e);
+                return;
+            }
+        };
+        let () = match server.base_url() {
+            Ok(url) => {
+                println!("KOTO_ACCOUNTS_URL={}", url);
+                println!("KOTO_API_URL={}/v1", url);
+            }
+            Err(e) => {
+                eprintln!("Error {}", e);
+                return;
+            }
+        };
+        if let Err(e) = server.run().await {
+            eprintln!("Error {}", e);
+        }
+    }
+}
@@ -17,24 +17,37 @@
 
 use clap::{Parser, Subcommand};
 
-use crate::config::KOTO_VERSION;
+use crate::{config::KOTO_VERSION, libs::output::OutputFormat};
 
+pub mod analysis;
+pub mod cache;
 pub mod create_m3u;
 pub mod cuesheet;
 pub mod edit;
 pub mod init;
 pub mod list;
+pub mod mock_server;
+pub mod related;
 pub mod search;
 
 #[derive(Parser)]
 #[clap(author, version = KOTO_VERSION, about, long_about = None)]
 pub struct Koto {
+    /// Don't use the cached api responses
+    #[clap(long, global = true)]
+    pub no_cache: bool,
+    /// Format of the printed results
+    #[clap(long, global = true, arg_enum, default_value = "text")]
+    pub output_format: OutputFormat,
     #[clap(subcommand)]
     pub subcommand: KotoSubcommands,
 }
 
 #[derive(Subcommand)]
 pub enum KotoSubcommands {
+    Analysis(analysis::Analysis),
+    #[clap(subcommand)]
+    Cache(cache::CacheSubcommand),
     CreateM3U(create_m3u::CreateM3U),
     #[clap(subcommand)]
     CueSheet(cuesheet::CueSheetSubcommand),
@@ -42,23 +55,30 @@ pub enum KotoSubcommands {
     Init(init::Init),
     Search(search::Search),
     List(list::List),
+    Related(related::Related),
+    #[clap(hide = true)]
+    MockServer(mock_server::MockServerCommand),
 }
 
 impl KotoSubcommands {
-    pub async fn run(self) {
+    pub async fn run(self, no_cache: bool, output_format: OutputFormat) {
         match self {
+            KotoSubcommands::Analysis(analysis) => analysis.run(no_cache).await,
+            KotoSubcommands::Cache(cache) => cache.run(output_format),
             KotoSubcommands::CreateM3U(m3u) => m3u.run(),
-            KotoSubcommands::CueSheet(cue) => cue.run().await,
-            KotoSubcommands::Edit(edit) => edit.run(),
+            KotoSubcommands::CueSheet(cue) => cue.run(no_cache, output_format).await,
+            KotoSubcommands::Edit(edit) => edit.run(no_cache).await,
             KotoSubcommands::Init(init) => init.run(),
-            KotoSubcommands::Search(search) => search.run().await,
-            KotoSubcommands::List(list) => list.run().await,
+            KotoSubcommands::Search(search) => search.run(no_cache, output_format).await,
+            KotoSubcommands::List(list) => list.run(no_cache, output_format).await,
+            KotoSubcommands::Related(related) => related.run(no_cache).await,
+            KotoSubcommands::MockServer(mock) => mock.run().await,
         }
     }
 }
 
 impl Koto {
     pub async fn run(self) {
-        self.subcommand.run().await
+        self.subcommand.run(self.no_cache, self.output_format).await
     }
 }
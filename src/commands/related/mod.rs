@@ -0,0 +1,144 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::{fs::File, io::Write, process::exit};
+
+use clap::{ArgEnum, Parser};
+
+use crate::{
+    commands::list::db::{Artist, Artists},
+    config::{check_credential_exist, extend_env},
+    libs::{
+        picker,
+        related_graph::RelatedGraph,
+        spotify::{Spotify, SpotifySearchType},
+        spotify_id::{self, SpotifyIdType},
+    },
+};
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+pub enum GraphFormat {
+    Dot,
+    Graphml,
+    Json,
+}
+
+/// Crawl the related artists graph of an artist
+#[derive(Parser)]
+pub struct Related {
+    /// Search by artist id, url or uri
+    #[clap(long)]
+    id: bool,
+    /// Take the first search result instead of choosing it
+    #[clap(long)]
+    first: bool,
+    /// Number of hops from the artist
+    #[clap(short, long, default_value_t = 2)]
+    depth: u8,
+    /// Maximum number of artists in the graph
+    #[clap(short = 'n', long, default_value_t = 50)]
+    max_nodes: usize,
+    /// Export format
+    #[clap(short, long, arg_enum, default_value = "dot")]
+    export: GraphFormat,
+    /// Output file
+    #[clap(short, long, help = "Output file [stdout if not present]")]
+    output: Option<String>,
+    /// Follow the artists related to at least MIN_LINKS crawled artists
+    #[clap(long, value_name = "MIN_LINKS")]
+    seed_db: Option<usize>,
+    /// Artist name or id
+    artist: String,
+}
+
+impl Related {
+    pub async fn run(self, no_cache: bool) {
+        let Related {
+            id,
+            first,
+            depth,
+            max_nodes,
+            export,
+            output,
+            seed_db,
+            artist,
+        } = self;
+        let () = extend_env();
+        let () = match check_credential_exist() {
+            true => (),
+            false => return,
+        };
+        let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
+        let artist_id = match id {
+            true => spotify_id::parse(&artist, SpotifyIdType::Artist).unwrap_or_else(|e| e.exit()),
+            false => {
+                picker::search_and_pick(&spotify, &artist, SpotifySearchType::Artist, first, false)
+                    .await
+                    .unwrap_or_else(|e| e.exit())
+            }
+        };
+        let graph = RelatedGraph::crawl(&spotify, &artist_id, depth, max_nodes.max(1))
+            .await
+            .unwrap_or_else(|e| e.exit());
+        let content = match export {
+            GraphFormat::Dot => graph.to_dot(),
+            GraphFormat::Graphml => graph.to_graphml(),
+            GraphFormat::Json => serde_json::to_string_pretty(&graph).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                exit(1)
+            }),
+        };
+        match output {
+            Some(output) => {
+                let result =
+                    File::create(&output).and_then(|mut file| file.write_all(content.as_bytes()));
+                if let Err(e) = result {
+                    eprintln!("Unable to write {} : {}", output, e);
+                    exit(1)
+                }
+            }
+            None => println!("{}", content),
+        }
+        if let Some(min_links) = seed_db {
+            Self::seed_db(&spotify, &graph, min_links).await
+        }
+    }
+
+    /// Add the overlapping artists to the followed artists
+    async fn seed_db(spotify: &Spotify, graph: &RelatedGraph, min_links: usize) {
+        let mut db = match Artists::deserialize() {
+            Some(db) => db,
+            None => {
+                eprintln!("Cannot deserialize");
+                return;
+            }
+        };
+        for (node, links) in graph.overlapping(min_links) {
+            if db.contains(&node.id) {
+                continue;
+            }
+            match Artist::from_id(spotify, &node.id).await {
+                Ok(artist) => {
+                    eprintln!("Follow {} ({} links)", node.name, links);
+                    db.add(artist)
+                }
+                Err(e) => eprintln!("Unable to follow {} : {}", node.name, e),
+            }
+        }
+        db.save()
+    }
+}
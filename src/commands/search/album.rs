@@ -17,12 +17,81 @@
 
 use clap::Parser;
 
-/// Search content related to an artist
+use crate::libs::{
+    error::SpotifyError,
+    output::{self, OutputFormat},
+    query::SearchQuery,
+    spotify::{Spotify, SpotifySearchKey, SpotifySearchResultItem, SpotifySearchType},
+    spotify_id::{self, SpotifyIdType},
+    util,
+};
+
+/// Search an album
 #[derive(Debug, Parser)]
-pub struct Album {}
+pub struct Album {
+    #[clap(long)]
+    /// Search by album id, url or uri
+    id: bool,
+    #[clap(short, long, conflicts_with = "id")]
+    /// Only match the albums of this artist
+    artist: Option<String>,
+    #[clap(short, long)]
+    /// Display the album cover
+    graphic: bool,
+    /// Search item
+    album: String,
+}
 
 impl Album {
-    pub async fn run(self) {
-        unimplemented!("Album TODO")
+    pub async fn run(self, no_cache: bool, output_format: OutputFormat) {
+        let Album {
+            id,
+            artist,
+            graphic,
+            album,
+        } = self;
+        let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
+        let album_id = if id {
+            spotify_id::parse(&album, SpotifyIdType::Album).unwrap_or_else(|e| e.exit())
+        } else {
+            let query = match artist {
+                Some(artist) => SearchQuery::default()
+                    .album(Some(album))
+                    .artist(Some(artist))
+                    .build()
+                    .unwrap_or_else(|e| e.exit()),
+                None => album,
+            };
+            let result = spotify
+                .search(
+                    query.as_str(),
+                    vec![SpotifySearchType::Album],
+                    None,
+                    Some(1),
+                    Some(0),
+                    None,
+                )
+                .await
+                .unwrap_or_else(|e| e.exit());
+            result
+                .get(&SpotifySearchKey::Albums)
+                .and_then(|albums| {
+                    albums.items.iter().find_map(|ssri| match ssri {
+                        SpotifySearchResultItem::Album { id, .. } => Some(id.clone()),
+                        _ => None,
+                    })
+                })
+                .unwrap_or_else(|| SpotifyError::NotFound(None).exit())
+        };
+        let album = spotify.album(album_id).await.unwrap_or_else(|e| e.exit());
+        if !output_format.is_text() {
+            return output::print_albums(output_format, &[album]);
+        }
+        if graphic {
+            if let Some(image) = album.dynamic_image().await {
+                util::show_image(&image);
+            }
+        }
+        print!("{}", album.default_format());
     }
 }
@@ -15,12 +15,16 @@
 //                                                                                            //
 // /////////////////////////////////////////////////////////////////////////////////////////////
 
-use std::process::exit;
+use std::{collections::HashSet, path::PathBuf, process::exit};
 
 use clap::{ArgGroup, Parser};
 
 use crate::{
-    libs::spotify::{Spotify, SpotifyIncludeGroupe, SpotifySearchType},
+    commands::create_m3u::{default_extensions, M3UPlaylist},
+    libs::output::{self, AlbumRecord, ArtistRecord, OutputFormat, TrackRecord},
+    libs::picker,
+    libs::spotify::{Spotify, SpotifyIncludeGroupe, SpotifySearchType, Track},
+    libs::spotify_id::{self, SpotifyIdType},
     libs::util,
 };
 
@@ -29,8 +33,8 @@ use crate::{
 #[clap(group(
     ArgGroup::new("search_type")
     .required(false)
-    .args(&["albums"])
-    .conflicts_with("related_artists")
+    .args(&["albums", "top-tracks"])
+    .conflicts_with("related-artists")
 )) ]
 pub struct Artist {
     #[clap(short, long)]
@@ -40,13 +44,36 @@ pub struct Artist {
     /// Search related artists
     related_artists: bool,
     #[clap(long)]
-    /// Search by artist id
+    /// Search artist's top tracks
+    top_tracks: bool,
+    #[clap(long)]
+    /// Market of the top tracks and the albums
+    market: Option<String>,
+    #[clap(
+        long,
+        arg_enum,
+        use_value_delimiter = true,
+        default_value = "album,single,compilation,appears-on"
+    )]
+    /// Album groups of the discography
+    groups: Vec<SpotifyIncludeGroupe>,
+    #[clap(long, requires = "top-tracks")]
+    /// Write the local files matching the top tracks to a M3U playlist
+    m3u: Option<String>,
+    #[clap(long, requires = "m3u", default_value = ".")]
+    /// Directories where the local files are looked for
+    music_dir: Vec<String>,
+    #[clap(long)]
+    /// Search by artist id, url or uri
     id: bool,
+    #[clap(long)]
+    /// Take the first search result instead of choosing it
+    first: bool,
     #[clap(short, long)]
     /// Display graohic result (cover, picture, etc ...)
     graphic: bool,
     #[clap(short, long, default_value_t = 3)]
-    /// Result limit
+    /// Number of related artists
     limit: u32,
     /// Output column
     #[clap(short, long, default_value_t = 1)]
@@ -56,102 +83,103 @@ pub struct Artist {
 }
 
 impl Artist {
-    pub async fn run(self) {
+    pub async fn run(self, no_cache: bool, output_format: OutputFormat) {
         let Artist {
             albums,
             related_artists,
+            top_tracks,
+            market,
+            groups,
+            m3u,
+            music_dir,
             id,
+            first,
             graphic,
             limit,
             column,
             artist,
         } = self;
-        let spotify = Spotify::init().await;
-        let artist_id = if id {
-            artist
-        } else {
-            let result = spotify
-                .search(
-                    artist.as_str(),
-                    vec![SpotifySearchType::Artist],
-                    None,
-                    Some(1),
-                    Some(0),
-                    None,
-                )
+        let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
+        let artist_id = match id {
+            true => spotify_id::parse(&artist, SpotifyIdType::Artist).unwrap_or_else(|e| e.exit()),
+            false => picker::search_and_pick(
+                &spotify,
+                &artist,
+                SpotifySearchType::Artist,
+                first,
+                graphic,
+            )
+            .await
+            .unwrap_or_else(|e| e.exit()),
+        };
+        if top_tracks {
+            let tracks = spotify
+                .artist_top_tracks(&artist_id, market)
                 .await
-                .unwrap_or_else(|| {
-                    println!("Wrong Api Response");
-                    exit(1)
-                });
-            let items = result
-                .get(&crate::libs::spotify::SpotifySearchKey::Artists)
-                .unwrap_or_else(|| {
-                    println!("Unable to get the artist");
-                    exit(1)
-                });
-            let mut vec_artist_id = items
-                .items
-                .iter()
-                .filter_map(|ssri| match ssri {
-                    crate::libs::spotify::SpotifySearchResultItem::Artist {
-                        external_urls: _,
-                        followers: _,
-                        genres: _,
-                        href: _,
-                        id,
-                        images: _,
-                        name: _,
-                        popularity: _,
-                        artist_type: _,
-                        uri: _,
-                    } => Some(id.clone()),
-                    _ => None,
-                })
-                .collect::<Vec<String>>();
-            if !vec_artist_id.is_empty() {
-                vec_artist_id.remove(0)
-            } else {
-                println!("No artist returned");
-                exit(1)
+                .unwrap_or_else(|e| e.exit());
+            match output_format.is_text() {
+                true => Self::show_top_tracks(&tracks, graphic).await,
+                false => output::print_records(
+                    output_format,
+                    &tracks.iter().map(TrackRecord::from).collect::<Vec<_>>(),
+                ),
             }
-        };
+            if let Some(m3u) = m3u {
+                let () = Self::top_tracks_m3u(&tracks, &music_dir, &m3u);
+            }
+            return;
+        }
         match (albums, related_artists) {
             (true, true) => unreachable!("Albums and Related are mutualy exclued"),
             (true, false) => {
-                let _albums = spotify
-                    .artist_album(
-                        artist_id,
-                        vec![SpotifyIncludeGroupe::Album],
-                        Some(limit),
-                        None,
-                        None,
-                    )
+                let mut albums = spotify
+                    .artist_albums(artist_id, groups.clone(), market)
+                    .collect()
                     .await
-                    .unwrap_or_else(|| {
-                        println!("Unable to fetch the related artist");
-                        exit(1)
-                    });
+                    .unwrap_or_else(|e| e.exit());
+                let () = albums.sort_by(|a, b| a.release_date.cmp(&b.release_date));
+                let mut editions = HashSet::new();
+                let () = albums.retain(|album| editions.insert(album.edition_key()));
+                match output_format.is_text() {
+                    true => util::display_discography(&albums, &groups, column, graphic).await,
+                    false => output::print_records(
+                        output_format,
+                        &albums
+                            .iter()
+                            .filter(|album| album.group().map_or(false, |g| groups.contains(&g)))
+                            .map(AlbumRecord::from)
+                            .collect::<Vec<_>>(),
+                    ),
+                }
             }
             (false, true) => {
-                let related_artists =
-                    spotify
-                        .related_artists(&artist_id)
-                        .await
-                        .unwrap_or_else(|| {
-                            println!("Unable to fetch the related artist");
-                            exit(1)
-                        });
+                let related_artists = spotify
+                    .related_artists(&artist_id)
+                    .await
+                    .unwrap_or_else(|e| e.exit());
+                if !output_format.is_text() {
+                    return output::print_records(
+                        output_format,
+                        &related_artists
+                            .iter()
+                            .take(limit as usize)
+                            .map(ArtistRecord::from)
+                            .collect::<Vec<_>>(),
+                    );
+                }
                 let _ =
                     util::display_related_artist(&related_artists, column, limit as usize, graphic)
                         .await;
                 return;
             }
             (false, false) => {
-                let artist = spotify.artist(artist_id.as_str()).await.unwrap_or_else(|| {
-                    println!("Unable to retrieve the artist");
-                    exit(1)
-                });
+                let artist = spotify
+                    .artist(artist_id.as_str())
+                    .await
+                    .unwrap_or_else(|e| e.exit());
+                if !output_format.is_text() {
+                    return output::print_records(output_format, &[ArtistRecord::from(&artist)]);
+                }
                 println!("Name  : {}", artist.name);
                 println!("Genre : {}", artist.genres.join("\n        "));
                 if let Some(map) = artist.images.get(0) {
@@ -166,4 +194,116 @@ impl Artist {
             }
         }
     }
+
+    async fn show_top_tracks(tracks: &[Track], graphic: bool) {
+        println!(
+            "{:>3}  {:<40} {:>8}  {:<30} {:>10}",
+            "#", "Title", "Duration", "Album", "Popularity"
+        );
+        for (index, track) in tracks.iter().enumerate() {
+            if graphic {
+                if let Some(image) = track.album_image().await {
+                    util::show_image(&image);
+                }
+            }
+            println!(
+                "{:>3}  {:<40} {:>8}  {:<30} {:>10}",
+                index + 1,
+                track.name,
+                track.duration(),
+                track.album.name,
+                track.popularity
+            );
+        }
+    }
+
+    /// Lowercase alphanumeric characters, to compare titles and file names
+    fn normalize(s: &str) -> String {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(|c| c.to_lowercase())
+            .collect()
+    }
+
+    /// Whether the local file is the track, with the artist name in its stem or not.
+    /// The normalized stem, without its leading track number, must be the title,
+    /// optionally preceded or followed by the artist name
+    fn file_match(file: &str, title: &str, artist: &str) -> Option<bool> {
+        let stem = PathBuf::from(file)
+            .file_stem()
+            .map(|stem| Self::normalize(&stem.to_string_lossy()))?;
+        let numberless = stem.trim_start_matches(|c: char| c.is_ascii_digit());
+        let stems = [stem.as_str(), numberless];
+        if stems.contains(&title) {
+            return Some(false);
+        }
+        let with_artist = !artist.is_empty()
+            && stems.iter().any(|stem| {
+                stem.strip_prefix(artist) == Some(title) || stem.strip_suffix(artist) == Some(title)
+            });
+        with_artist.then_some(true)
+    }
+
+    /// Local file of the track, the one with the artist name in it is preferred
+    fn local_file<'a>(files: &'a [String], track: &Track) -> Option<&'a String> {
+        let title = Self::normalize(&track.name);
+        // A title without any alphanumeric character would match every file
+        if title.is_empty() {
+            return None;
+        }
+        let artist = Self::normalize(&track.artists_name());
+        files
+            .iter()
+            .filter_map(|file| Some((file, Self::file_match(file, &title, &artist)?)))
+            .min_by_key(|(_, with_artist)| !with_artist)
+            .map(|(file, _)| file)
+    }
+
+    fn top_tracks_m3u(tracks: &[Track], music_dirs: &[String], output: &str) {
+        let mut library = M3UPlaylist::new();
+        let extensions = default_extensions();
+        for music_dir in music_dirs {
+            let () = library.append_sub_dir(&extensions, &PathBuf::from(music_dir));
+        }
+        let mut playlist = M3UPlaylist::new();
+        for track in tracks {
+            match Self::local_file(&library.items, track) {
+                Some(path) => playlist.append_path(path),
+                None => eprintln!("No local file for {}", track.name),
+            }
+        }
+        if playlist.items.is_empty() {
+            eprintln!("No matched files");
+            return;
+        }
+        playlist.save(output).unwrap_or_else(|e| {
+            eprintln!("Unable to write {} : {}", output, e);
+            exit(1)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Artist;
+
+    fn file_match(file: &str) -> Option<bool> {
+        Artist::file_match(file, "intro", "daftpunk")
+    }
+
+    #[test]
+    fn full_title() {
+        assert_eq!(file_match("music/Intro.flac"), Some(false));
+        assert_eq!(file_match("music/01 - Intro.mp3"), Some(false));
+        assert_eq!(file_match("music/01 Daft Punk - Intro.mp3"), Some(true));
+        assert_eq!(file_match("music/Intro (Daft Punk).m4a"), Some(true));
+    }
+
+    #[test]
+    fn partial_title() {
+        assert_eq!(file_match("music/Intro (Reprise).flac"), None);
+        assert_eq!(file_match("music/Outro.flac"), None);
+        assert_eq!(file_match("music/Daft Punk - Intro (Live).flac"), None);
+        assert_eq!(Artist::file_match("1999.flac", "1999", ""), Some(false));
+    }
 }
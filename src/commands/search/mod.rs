@@ -19,11 +19,16 @@ use clap::{Parser, Subcommand};
 
 use crate::{
     config::{check_credential_exist, extend_env},
-    libs::spotify::{Spotify, SpotifySearchType},
+    libs::{
+        output::{self, OutputFormat, SearchRecord},
+        query::{Isrc, SearchQuery, SearchTag, Upc, YearRange},
+        spotify::{Spotify, SpotifySearchType},
+    },
 };
 
 pub mod album;
 pub mod artist;
+pub mod new_releases;
 pub mod track;
 
 /// Search for an item
@@ -59,8 +64,34 @@ pub struct Search {
     /// offset the result
     #[clap(long)]
     offset: Option<u32>,
+
+    /// Only match this artist name
+    #[clap(long)]
+    artist_name: Option<String>,
+    /// Only match this album name
+    #[clap(long)]
+    album_name: Option<String>,
+    /// Only match a year or a range of years (1990-1999)
+    #[clap(long)]
+    year: Option<YearRange>,
+    /// Only match this genre (artists and tracks)
+    #[clap(long)]
+    genre: Option<String>,
+    /// Only match this isrc
+    #[clap(long, requires = "track")]
+    isrc: Option<Isrc>,
+    /// Only match this upc
+    #[clap(long, requires = "album")]
+    upc: Option<Upc>,
+    /// Only match the new or the least popular albums
+    #[clap(long, arg_enum, multiple_occurrences = true, requires = "album")]
+    tag: Vec<SearchTag>,
+    /// Show the query sent to spotify
+    #[clap(short, long)]
+    verbose: bool,
+
     /// search item
-    #[clap(required = true)]
+    #[clap(required_unless_present_any = &["artist-name", "album-name", "year", "genre", "isrc", "upc", "tag"])]
     item: Option<String>,
 }
 
@@ -69,20 +100,24 @@ pub enum SearchSubCommand {
     Artist(artist::Artist),
     Album(album::Album),
     Track(track::Track),
+    NewReleases(new_releases::NewReleases),
 }
 
 impl SearchSubCommand {
-    pub async fn run(self) {
+    pub async fn run(self, no_cache: bool, output_format: OutputFormat) {
         match self {
-            SearchSubCommand::Artist(artist) => artist.run().await,
-            SearchSubCommand::Album(album) => album.run().await,
-            SearchSubCommand::Track(track) => track.run().await,
+            SearchSubCommand::Artist(artist) => artist.run(no_cache, output_format).await,
+            SearchSubCommand::Album(album) => album.run(no_cache, output_format).await,
+            SearchSubCommand::Track(track) => track.run(no_cache, output_format).await,
+            SearchSubCommand::NewReleases(new_releases) => {
+                new_releases.run(no_cache, output_format).await
+            }
         }
     }
 }
 
 impl Search {
-    pub async fn run(self) {
+    pub async fn run(self, no_cache: bool, output_format: OutputFormat) {
         let () = extend_env();
         let () = match check_credential_exist() {
             true => (),
@@ -90,12 +125,12 @@ impl Search {
         };
 
         match self.search_subcommand {
-            Some(sub) => sub.run().await,
-            None => self.run_search().await,
+            Some(sub) => sub.run(no_cache, output_format).await,
+            None => self.run_search(no_cache, output_format).await,
         }
     }
 
-    pub async fn run_search(self) {
+    pub async fn run_search(self, no_cache: bool, output_format: OutputFormat) {
         let Search {
             search_subcommand: _,
             artist,
@@ -105,9 +140,29 @@ impl Search {
             limit,
             graphic,
             offset,
+            artist_name,
+            album_name,
+            year,
+            genre,
+            isrc,
+            upc,
+            tag,
+            verbose,
             item,
         } = self;
-        let item = item.unwrap_or("".into());
+        let query = SearchQuery::new(item)
+            .artist(artist_name)
+            .album(album_name)
+            .year(year)
+            .genre(genre)
+            .isrc(isrc)
+            .upc(upc)
+            .tags(&tag)
+            .build()
+            .unwrap_or_else(|e| e.exit());
+        if verbose {
+            eprintln!("****   Query   : {}", query)
+        }
         let mut ressource_types = vec![];
         if artist {
             ressource_types.push(SpotifySearchType::Artist)
@@ -118,10 +173,10 @@ impl Search {
         if album {
             ressource_types.push(SpotifySearchType::Album)
         }
-        let spotify = Spotify::init().await;
+        let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
         let result = spotify
             .search(
-                item.as_str(),
+                query.as_str(),
                 ressource_types,
                 market,
                 limit.map(|l| if l > 50 { 50 } else { l }),
@@ -130,15 +185,19 @@ impl Search {
             )
             .await;
         let result = match result {
-            Some(res) => res,
-            None => {
-                println!("No result");
-                return;
-            }
+            Ok(res) => res,
+            Err(e) => e.exit(),
         };
+        if !output_format.is_text() {
+            let records = result
+                .values()
+                .flat_map(|ssr| ssr.items.iter().map(SearchRecord::from))
+                .collect::<Vec<_>>();
+            return output::print_records(output_format, &records);
+        }
         for (_, ssr) in result.iter() {
             if ssr.items.is_empty() {
-                println!("\n****   No Result   ****\n")
+                eprintln!("\n****   No Result   ****\n")
             } else {
                 ssr.show_spotify_search_result(graphic).await;
             }
@@ -0,0 +1,183 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::collections::{HashMap, HashSet};
+
+use clap::Parser;
+
+use crate::{
+    commands::list::db::Artists,
+    libs::{
+        error::SpotifyResult,
+        output::{self, AlbumRecord, OutputFormat},
+        spotify::{AlbumItems, Spotify},
+        util,
+    },
+};
+
+/// Number of releases whose artists are fetched at once
+const RELEASES_BATCH_SIZE: usize = 50;
+
+/// Browse the new releases
+#[derive(Debug, Parser)]
+pub struct NewReleases {
+    #[clap(long)]
+    /// Market of the releases
+    market: Option<String>,
+    #[clap(short, long, default_value_t = 20)]
+    /// Result limit
+    limit: usize,
+    #[clap(long)]
+    /// Offset of the first release
+    offset: Option<u32>,
+    #[clap(long)]
+    /// Only keep the releases of unfollowed artists sharing a genre with the followed artists
+    followed_genres: bool,
+    #[clap(short, long)]
+    /// Display graohic result (cover, picture, etc ...)
+    graphic: bool,
+    /// Output column
+    #[clap(short, long, default_value_t = 1)]
+    column: usize,
+}
+
+/// Genres and ids of the followed artists
+struct Followed {
+    ids: HashSet<String>,
+    genres: HashSet<String>,
+}
+
+impl Followed {
+    async fn fetch(spotify: &Spotify) -> SpotifyResult<Option<Self>> {
+        let ids = match Artists::deserialize() {
+            Some(db) => db.artist_ids(),
+            None => return Ok(None),
+        };
+        let genres = spotify
+            .artists(&ids)
+            .await?
+            .into_iter()
+            .flat_map(|artist| artist.genres)
+            .collect::<HashSet<_>>();
+        Ok(Some(Self {
+            ids: ids.into_iter().collect(),
+            genres,
+        }))
+    }
+
+    /// Keep the releases whose artists aren't followed but share a genre with the followed ones
+    async fn adjacent(
+        &self,
+        spotify: &Spotify,
+        releases: Vec<AlbumItems>,
+    ) -> SpotifyResult<Vec<AlbumItems>> {
+        let artist_ids = releases
+            .iter()
+            .flat_map(AlbumItems::artist_ids)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let genres = spotify
+            .artists(&artist_ids)
+            .await?
+            .into_iter()
+            .map(|artist| (artist.id, artist.genres))
+            .collect::<HashMap<_, _>>();
+        Ok(releases
+            .into_iter()
+            .filter(|album| {
+                let ids = album.artist_ids();
+                !ids.iter().any(|id| self.ids.contains(id))
+                    && ids
+                        .iter()
+                        .filter_map(|id| genres.get(id))
+                        .flatten()
+                        .any(|genre| self.genres.contains(genre))
+            })
+            .collect())
+    }
+}
+
+impl NewReleases {
+    pub async fn run(self, no_cache: bool, output_format: OutputFormat) {
+        let NewReleases {
+            market,
+            limit,
+            offset,
+            followed_genres,
+            graphic,
+            column,
+        } = self;
+        let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
+        let mut releases = spotify.new_releases(market, offset);
+        let albums = match followed_genres {
+            false => releases
+                .max_items(Some(limit))
+                .collect()
+                .await
+                .unwrap_or_else(|e| e.exit()),
+            true => {
+                let followed = match Followed::fetch(&spotify).await {
+                    Ok(Some(followed)) => followed,
+                    Ok(None) => {
+                        eprintln!("Cannot deserialize");
+                        return;
+                    }
+                    Err(e) => e.exit(),
+                };
+                if followed.genres.is_empty() {
+                    eprintln!("The followed artists don't have any genre");
+                    return;
+                }
+                let mut albums = vec![];
+                let mut batch = vec![];
+                while albums.len() < limit {
+                    let release = releases
+                        .next()
+                        .await
+                        .transpose()
+                        .unwrap_or_else(|e| e.exit());
+                    let done = release.is_none();
+                    batch.extend(release);
+                    if batch.len() == RELEASES_BATCH_SIZE || (done && !batch.is_empty()) {
+                        let adjacent = followed
+                            .adjacent(&spotify, std::mem::take(&mut batch))
+                            .await
+                            .unwrap_or_else(|e| e.exit());
+                        albums.extend(adjacent);
+                    }
+                    if done {
+                        break;
+                    }
+                }
+                albums.truncate(limit);
+                albums
+            }
+        };
+        if !output_format.is_text() {
+            return output::print_records(
+                output_format,
+                &albums.iter().map(AlbumRecord::from).collect::<Vec<_>>(),
+            );
+        }
+        if albums.is_empty() {
+            eprintln!("\n****   No Result   ****\n");
+            return;
+        }
+        util::display_album_grid(&albums.iter().collect::<Vec<_>>(), column, true, graphic).await
+    }
+}
@@ -17,12 +17,138 @@
 
 use clap::Parser;
 
-/// Search content related to an artist
+use crate::{
+    commands::edit::{Edit, FileType},
+    libs::{
+        error::SpotifyError,
+        output::{self, OutputFormat, TrackRecord},
+        query::{Isrc, SearchQuery},
+        spotify::Spotify,
+        spotify_id::{self, SpotifyIdType},
+    },
+};
+
+/// Search a track
 #[derive(Debug, Parser)]
-pub struct Track {}
+pub struct Track {
+    #[clap(long)]
+    /// Search by track id, url or uri
+    id: bool,
+    #[clap(long, conflicts_with = "id")]
+    /// Only match the track with this isrc
+    isrc: Option<Isrc>,
+    #[clap(short, long, conflicts_with = "id")]
+    /// Only match the tracks of this artist
+    artist: Option<String>,
+    #[clap(long, conflicts_with = "id")]
+    /// Only match the tracks of this album
+    album: Option<String>,
+    #[clap(long)]
+    /// market to look for
+    market: Option<String>,
+    #[clap(short, long, default_value_t = 1)]
+    /// Result limit
+    limit: u8,
+    #[clap(short, long)]
+    /// Show the audio features (tempo, key, energy, ...)
+    features: bool,
+    #[clap(long)]
+    /// Show the album of the track
+    album_context: bool,
+    #[clap(long, value_name = "FILE")]
+    /// Tag the file with the first track found, with the bpm and key if --features is set
+    edit: Option<String>,
+    #[clap(long = "type", arg_enum)]
+    /// Type of the edited file, detected from its content if not given
+    file_type: Option<FileType>,
+    /// Search item
+    #[clap(required_unless_present_any = &["isrc", "artist", "album"])]
+    track: Option<String>,
+}
 
 impl Track {
-    pub async fn run(self) {
-        unimplemented!("TRACK TODO")
+    fn query(&self) -> String {
+        SearchQuery::new(self.track.clone())
+            .isrc(self.isrc.clone())
+            .artist(self.artist.clone())
+            .album(self.album.clone())
+            .build()
+            .unwrap_or_else(|e| e.exit())
+    }
+
+    pub async fn run(self, no_cache: bool, output_format: OutputFormat) {
+        let spotify = Spotify::init(no_cache).await.unwrap_or_else(|e| e.exit());
+        let tracks = match (self.id, &self.track) {
+            (true, Some(track_id)) => {
+                let track_id =
+                    spotify_id::parse(track_id, SpotifyIdType::Track).unwrap_or_else(|e| e.exit());
+                vec![spotify.track(&track_id).await.unwrap_or_else(|e| e.exit())]
+            }
+            _ => spotify
+                .search_tracks(
+                    self.query().as_str(),
+                    self.market.clone(),
+                    Some(self.limit.min(50)),
+                )
+                .await
+                .unwrap_or_else(|e| e.exit()),
+        };
+        if tracks.is_empty() {
+            SpotifyError::NotFound(None).exit()
+        }
+        let features = match self.features {
+            true => {
+                let ids = tracks
+                    .iter()
+                    .map(|track| track.id.clone())
+                    .collect::<Vec<_>>();
+                spotify
+                    .audio_features_batch(&ids)
+                    .await
+                    .unwrap_or_else(|e| e.exit())
+            }
+            false => vec![None; tracks.len()],
+        };
+        let mut records = vec![];
+        let mut albums = vec![];
+        for (track, features) in tracks.iter().zip(features) {
+            let album = match self.album_context {
+                true => Some(
+                    spotify
+                        .album(track.album.id.clone())
+                        .await
+                        .unwrap_or_else(|e| e.exit()),
+                ),
+                false => None,
+            };
+            if output_format.is_text() {
+                print!("{}", track.default_format());
+                if let Some(features) = &features {
+                    print!("{}", features.default_format());
+                }
+                if let Some(album) = &album {
+                    print!("{}", album.default_format());
+                }
+                println!()
+            } else {
+                let record = TrackRecord::from(track);
+                records.push(match &features {
+                    Some(features) => record.with_features(features),
+                    None => record,
+                });
+                albums.extend(album);
+            }
+        }
+        if !output_format.is_text() {
+            match self.album_context {
+                true => output::print_albums(output_format, &albums),
+                false => output::print_records(output_format, &records),
+            }
+        }
+        if let Some(file) = self.edit {
+            Edit::from_track(self.file_type, file, &tracks[0], self.features)
+                .run(no_cache)
+                .await
+        }
     }
 }
@@ -17,10 +17,21 @@
 
 use xdg::BaseDirectories;
 
+use crate::libs::cassette::CassetteMode;
+
 pub const KOTO_VERSION: &'static str = env!("CARGO_PKG_VERSION");
 pub const KOTO_NAME: &'static str = env!("CARGO_PKG_NAME");
 pub const KOTO_DB_NAME: &'static str = "db.json";
 pub const KOTO_ENV: &'static str = ".env";
+pub const KOTO_TOKEN_CACHE: &'static str = "token.json";
+pub const KOTO_REQUESTS_PER_SECOND: &'static str = "KOTO_REQUESTS_PER_SECOND";
+pub const KOTO_MAX_RETRIES: &'static str = "KOTO_MAX_RETRIES";
+pub const KOTO_API_URL: &'static str = "KOTO_API_URL";
+pub const KOTO_ACCOUNTS_URL: &'static str = "KOTO_ACCOUNTS_URL";
+pub const KOTO_CASSETTE_MODE: &'static str = "KOTO_CASSETTE_MODE";
+pub const KOTO_CASSETTE: &'static str = "KOTO_CASSETTE";
+pub const SPOTIFY_API_URL: &'static str = "https://api.spotify.com/v1";
+pub const SPOTIFY_ACCOUNTS_URL: &'static str = "https://accounts.spotify.com";
 
 pub fn koto_base_dir() -> BaseDirectories {
     xdg::BaseDirectories::with_prefix(KOTO_NAME)
@@ -41,6 +52,10 @@ pub fn extend_env() {
 }
 
 pub fn check_credential_exist() -> bool {
+    // Replayed cassettes don't need credentials
+    if let Some(CassetteMode::Replay) = CassetteMode::from_env() {
+        return true;
+    }
     if let None = std::env::var("CLIENT_ID").ok() {
         println!("CLIENT_ID key not found\nYou should maybe run koto init");
         return false;
@@ -52,3 +67,22 @@ pub fn check_credential_exist() -> bool {
 
     return true;
 }
+
+fn base_url(key: &str, default: &str) -> String {
+    std::env::var(key)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or(default.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Base url of the spotify web api, overridden by KOTO_API_URL
+pub fn api_base_url() -> String {
+    base_url(KOTO_API_URL, SPOTIFY_API_URL)
+}
+
+/// Base url of the spotify accounts service, overridden by KOTO_ACCOUNTS_URL
+pub fn accounts_base_url() -> String {
+    base_url(KOTO_ACCOUNTS_URL, SPOTIFY_ACCOUNTS_URL)
+}
@@ -0,0 +1,201 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::{
+    fs::{self, OpenOptions},
+    path::PathBuf,
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::koto_base_dir, libs::spotify::SpotifyRessourceType};
+
+const RESPONSES_DIR: &'static str = "responses";
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Unix timestamp of the fetch
+    fetched_at: i64,
+    body: String,
+}
+
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    pub(crate) entries: usize,
+    pub(crate) expired: usize,
+    pub(crate) size: u64,
+}
+
+/// On-disk cache of the api responses, keyed by ressource type and spotify id
+pub struct ResponseCache {
+    /// Whether fresh entries are used. The responses are still saved when disabled
+    enabled: bool,
+}
+
+impl ResponseCache {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    fn directory(r_type: &SpotifyRessourceType) -> PathBuf {
+        PathBuf::from(RESPONSES_DIR).join(r_type.cache_name())
+    }
+
+    /// Ids are used as file name, anything else is hashed
+    pub fn key(id: &str, query: Option<&str>) -> String {
+        let is_plain = |s: &str| s.chars().all(|c| c.is_ascii_alphanumeric());
+        let id = match is_plain(id) && !id.is_empty() {
+            true => id.to_string(),
+            false => Self::hash(id),
+        };
+        match query.filter(|query| !query.is_empty()) {
+            Some(query) => format!("{}-{}", id, Self::hash(query)),
+            None => id,
+        }
+    }
+
+    /// 64 bits FNV-1a hash, stable across rust releases unlike the std hashers
+    fn hash(s: &str) -> String {
+        let hash = s.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        });
+        format!("{:016x}", hash)
+    }
+
+    fn read_entry(path: &PathBuf) -> Option<CacheEntry> {
+        let file = OpenOptions::new().read(true).open(path).ok()?;
+        serde_json::from_reader(file).ok()
+    }
+
+    fn is_expired(r_type: &SpotifyRessourceType, entry: &CacheEntry) -> bool {
+        Utc::now().timestamp() - entry.fetched_at > r_type.cache_ttl()
+    }
+
+    /// Cached body of the ressource if it's still fresh
+    pub fn get(&self, r_type: &SpotifyRessourceType, key: &str) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        let path = koto_base_dir().find_cache_file(Self::directory(r_type).join(key))?;
+        let entry = Self::read_entry(&path)?;
+        match Self::is_expired(r_type, &entry) {
+            true => None,
+            false => Some(entry.body),
+        }
+    }
+
+    /// Cached body of the ressource even if expired, used when the api can't be reached
+    pub fn get_stale(&self, r_type: &SpotifyRessourceType, key: &str) -> Option<String> {
+        let path = koto_base_dir().find_cache_file(Self::directory(r_type).join(key))?;
+        Self::read_entry(&path).map(|entry| entry.body)
+    }
+
+    pub fn put(&self, r_type: &SpotifyRessourceType, key: &str, body: &str) {
+        let path = match koto_base_dir().place_cache_file(Self::directory(r_type).join(key)) {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+        let entry = CacheEntry {
+            fetched_at: Utc::now().timestamp(),
+            body: body.to_string(),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path);
+        if let Ok(file) = file {
+            let _ = serde_json::to_writer(file, &entry);
+        }
+    }
+
+    fn entries(r_type: &SpotifyRessourceType) -> Vec<PathBuf> {
+        let directory = koto_base_dir()
+            .get_cache_home()
+            .join(Self::directory(r_type));
+        fs::read_dir(directory)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Remove the cached responses of the given ressource types.
+    /// Return the number of removed entries
+    pub fn clear(r_types: &[SpotifyRessourceType]) -> usize {
+        r_types
+            .iter()
+            .flat_map(Self::entries)
+            .filter(|path| fs::remove_file(path).is_ok())
+            .count()
+    }
+
+    pub fn stats(r_type: &SpotifyRessourceType) -> CacheStats {
+        Self::entries(r_type)
+            .iter()
+            .fold(CacheStats::default(), |mut stats, path| {
+                stats.entries += 1;
+                stats.size += fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+                let expired = Self::read_entry(path)
+                    .map(|entry| Self::is_expired(r_type, &entry))
+                    .unwrap_or(true);
+                if expired {
+                    stats.expired += 1
+                }
+                stats
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResponseCache;
+
+    #[test]
+    fn hash_is_stable() {
+        // Reference values of the 64 bits FNV-1a
+        assert_eq!(ResponseCache::hash(""), "cbf29ce484222325");
+        assert_eq!(ResponseCache::hash("a"), "af63dc4c8601ec8c");
+        assert_eq!(ResponseCache::hash("foobar"), "85944171f73967e8");
+    }
+
+    #[test]
+    fn key() {
+        assert_eq!(
+            ResponseCache::key("4tZwfgrHOc3mvqYlEYSvVN", None),
+            "4tZwfgrHOc3mvqYlEYSvVN"
+        );
+        assert_eq!(
+            ResponseCache::key("4tZwfgrHOc3mvqYlEYSvVN", Some("market=FR")),
+            format!(
+                "4tZwfgrHOc3mvqYlEYSvVN-{}",
+                ResponseCache::hash("market=FR")
+            )
+        );
+        assert_eq!(
+            ResponseCache::key("daft punk", Some("")),
+            ResponseCache::hash("daft punk")
+        );
+    }
+}
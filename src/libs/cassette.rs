@@ -0,0 +1,220 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::{collections::HashMap, fs::OpenOptions, path::PathBuf, sync::Mutex};
+
+use reqwest::{
+    header::{CONTENT_TYPE, RETRY_AFTER},
+    Method, Response, StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{KOTO_CASSETTE, KOTO_CASSETTE_MODE},
+    libs::error::{SpotifyError, SpotifyResult},
+};
+
+/// Path of the token exchange, never recorded since its response holds a live access token
+const TOKEN_PATH: &str = "/api/token";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Save every exchange with the api
+    Record,
+    /// Answer the requests with the saved exchanges, without any network access
+    Replay,
+}
+
+impl CassetteMode {
+    pub fn from_env() -> Option<Self> {
+        match std::env::var(KOTO_CASSETTE_MODE)
+            .ok()?
+            .to_lowercase()
+            .as_str()
+        {
+            "record" => Some(Self::Record),
+            "replay" => Some(Self::Replay),
+            _ => None,
+        }
+    }
+}
+
+/// A recorded request and its response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    method: String,
+    url: String,
+    status: u16,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+impl Interaction {
+    fn matches(&self, method: &Method, url: &Url) -> bool {
+        self.method == method.as_str() && self.url == url.as_str()
+    }
+
+    fn response(&self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in self.headers.iter() {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let response = builder
+            .body(self.body.clone())
+            .unwrap_or_else(|_| http::Response::new(self.body.clone()));
+        Response::from(response)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+/// File holding the http exchanges with the spotify api
+pub struct Cassette {
+    mode: CassetteMode,
+    path: PathBuf,
+    interactions: Mutex<Vec<Interaction>>,
+    /// Interactions already replayed
+    played: Mutex<Vec<bool>>,
+}
+
+impl Cassette {
+    /// Headers kept in the records, the others (Authorization, ...) are dropped
+    const RECORDED_HEADERS: [reqwest::header::HeaderName; 2] = [CONTENT_TYPE, RETRY_AFTER];
+
+    /// Load the cassette set with KOTO_CASSETTE_MODE and KOTO_CASSETTE
+    pub fn from_env() -> SpotifyResult<Option<Self>> {
+        let (mode, path) = match (CassetteMode::from_env(), std::env::var(KOTO_CASSETTE)) {
+            (Some(mode), Ok(path)) => (mode, PathBuf::from(path)),
+            _ => return Ok(None),
+        };
+        let interactions = match mode {
+            CassetteMode::Record => vec![],
+            CassetteMode::Replay => {
+                let file = OpenOptions::new().read(true).open(&path).map_err(|e| {
+                    SpotifyError::Cassette(format!("Cannot open the cassette {:?}: {}", path, e))
+                })?;
+                serde_json::from_reader::<_, CassetteFile>(file)
+                    .map_err(|e| {
+                        SpotifyError::Cassette(format!("Malformed cassette {:?}: {}", path, e))
+                    })?
+                    .interactions
+            }
+        };
+        let played = vec![false; interactions.len()];
+        Ok(Some(Self {
+            mode,
+            path,
+            interactions: Mutex::new(interactions),
+            played: Mutex::new(played),
+        }))
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// Answer with the first recorded response not yet replayed for this request.
+    /// Once they have all been replayed, the last one is used again
+    pub fn replay(&self, method: &Method, url: &Url) -> Response {
+        let interactions = self.interactions.lock().unwrap();
+        let mut played = self.played.lock().unwrap();
+        let matching = interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, interaction)| interaction.matches(method, url))
+            .map(|(index, _)| index)
+            .collect::<Vec<usize>>();
+        let index = matching
+            .iter()
+            .find(|index| !played[**index])
+            .or(matching.last());
+        match index {
+            Some(index) => {
+                played[*index] = true;
+                interactions[*index].response()
+            }
+            None => Interaction {
+                method: method.to_string(),
+                url: url.to_string(),
+                status: StatusCode::NOT_IMPLEMENTED.as_u16(),
+                headers: HashMap::new(),
+                body: format!(
+                    r#"{{"error": {{"status": 501, "message": "No recorded response for {} {}"}}}}"#,
+                    method, url
+                ),
+            }
+            .response(),
+        }
+    }
+
+    /// Save the exchange and give back an equivalent response.
+    /// The token exchange is passed through without being saved
+    pub async fn record(
+        &self,
+        method: &Method,
+        url: &Url,
+        response: Response,
+    ) -> reqwest::Result<Response> {
+        let status = response.status().as_u16();
+        let headers = Self::RECORDED_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = response.headers().get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect::<HashMap<String, String>>();
+        let body = response.text().await?;
+        let interaction = Interaction {
+            method: method.to_string(),
+            url: url.to_string(),
+            status,
+            headers,
+            body,
+        };
+        let response = interaction.response();
+        if url.path().ends_with(TOKEN_PATH) {
+            return Ok(response);
+        }
+        let mut interactions = self.interactions.lock().unwrap();
+        interactions.push(interaction);
+        let () = self.save(&interactions);
+        Ok(response)
+    }
+
+    /// The cassette is saved after each exchange since the commands may exit at any time
+    fn save(&self, interactions: &[Interaction]) {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(&self.path);
+        let cassette = CassetteFile {
+            interactions: interactions.to_vec(),
+        };
+        match file {
+            Ok(file) => {
+                let _ = serde_json::to_writer_pretty(file, &cassette);
+            }
+            Err(e) => eprintln!("Cannot write the cassette {:?}: {}", self.path, e),
+        }
+    }
+}
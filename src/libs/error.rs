@@ -0,0 +1,284 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::{fmt::Display, process::exit};
+
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize};
+
+pub type SpotifyResult<T> = Result<T, SpotifyError>;
+
+/// Error message sent back by the spotify api
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpotifyErrorBody {
+    pub(crate) status: u16,
+    pub(crate) message: String,
+}
+
+/// The web api and the accounts service don't report the errors with the same layout
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SpotifyErrorResponse {
+    Api {
+        error: SpotifyErrorBody,
+    },
+    Accounts {
+        error: String,
+        error_description: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+pub enum SpotifyError {
+    /// CLIENT_ID or CLIENT_SECRET isn't set
+    MissingCredentials,
+    /// The credentials or the token were rejected
+    BadCredentials(Option<SpotifyErrorBody>),
+    /// The requested ressource doesn't exist or the search returned nothing
+    NotFound(Option<SpotifyErrorBody>),
+    /// The request was still rate limited after all the retries
+    RateLimited(Option<SpotifyErrorBody>),
+    /// Any other unsuccessful http status
+    Http {
+        status: StatusCode,
+        body: Option<SpotifyErrorBody>,
+    },
+    /// The api response doesn't match the expected schema
+    Deserialize(serde_path_to_error::Error<serde_json::Error>),
+    /// The api couldn't be reached
+    Network(reqwest::Error),
+    /// The cassette to replay can't be read
+    Cassette(String),
+    /// The user quit the picker
+    Cancelled,
+}
+
+impl SpotifyError {
+    /// Create the error matching an unsuccessful response
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        let body = serde_json::from_str::<SpotifyErrorResponse>(body)
+            .ok()
+            .map(|response| match response {
+                SpotifyErrorResponse::Api { error } => error,
+                SpotifyErrorResponse::Accounts {
+                    error,
+                    error_description,
+                } => SpotifyErrorBody {
+                    status: status.as_u16(),
+                    message: error_description.unwrap_or(error),
+                },
+            });
+        match status {
+            StatusCode::UNAUTHORIZED => Self::BadCredentials(body),
+            StatusCode::BAD_REQUEST if Self::is_credential_error(&body) => {
+                Self::BadCredentials(body)
+            }
+            StatusCode::NOT_FOUND => Self::NotFound(body),
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited(body),
+            status => Self::Http { status, body },
+        }
+    }
+
+    fn is_credential_error(body: &Option<SpotifyErrorBody>) -> bool {
+        body.as_ref()
+            .map(|body| body.message.to_lowercase().contains("client"))
+            .unwrap_or(false)
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SpotifyError::NotFound(_) => 2,
+            SpotifyError::MissingCredentials | SpotifyError::BadCredentials(_) => 3,
+            SpotifyError::RateLimited(_) => 4,
+            SpotifyError::Network(_) => 5,
+            SpotifyError::Deserialize(_) => 6,
+            SpotifyError::Http { .. } => 7,
+            SpotifyError::Cassette(_) => 8,
+            SpotifyError::Cancelled => 130,
+        }
+    }
+
+    /// Print the error and exit with the code of its category
+    ///
+    /// Nothing is printed if the user cancelled
+    pub fn exit(&self) -> ! {
+        if !matches!(self, SpotifyError::Cancelled) {
+            eprintln!("{}", self);
+        }
+        exit(self.exit_code())
+    }
+}
+
+impl Display for SpotifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = |body: &Option<SpotifyErrorBody>| {
+            body.as_ref()
+                .map(|body| format!(": {} ({})", body.message, body.status))
+                .unwrap_or_default()
+        };
+        match self {
+            SpotifyError::MissingCredentials => write!(
+                f,
+                "CLIENT_ID or CLIENT_SECRET not found\nYou should maybe run koto init"
+            ),
+            SpotifyError::BadCredentials(body) => {
+                write!(f, "Credentials rejected by spotify{}", message(body))
+            }
+            SpotifyError::NotFound(body) => write!(f, "Not found{}", message(body)),
+            SpotifyError::RateLimited(body) => {
+                write!(f, "Rate limited by spotify, try later{}", message(body))
+            }
+            SpotifyError::Http { status, body } => {
+                write!(f, "Spotify api error {}{}", status, message(body))
+            }
+            SpotifyError::Deserialize(e) => {
+                write!(f, "Unexpected api response at {}: {}", e.path(), e.inner())
+            }
+            SpotifyError::Network(e) => write!(f, "Unable to reach the api: {}", e),
+            SpotifyError::Cassette(e) => write!(f, "{}", e),
+            SpotifyError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for SpotifyError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Network(e)
+    }
+}
+
+/// Deserialize a response body while keeping the path of the failing field
+pub fn from_json<T: DeserializeOwned>(body: &str) -> SpotifyResult<T> {
+    let deserializer = &mut serde_json::Deserializer::from_str(body);
+    serde_path_to_error::deserialize(deserializer).map_err(SpotifyError::Deserialize)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+
+    use super::{from_json, SpotifyError};
+
+    const API_BODY: &str = r#"{"error": {"status": 404, "message": "Non existing id"}}"#;
+
+    #[test]
+    fn unauthorized() {
+        let body = r#"{"error": {"status": 401, "message": "The access token expired"}}"#;
+        let error = SpotifyError::from_response(StatusCode::UNAUTHORIZED, body);
+        assert!(
+            matches!(&error, SpotifyError::BadCredentials(Some(body)) if body.status == 401),
+            "{:?}",
+            error
+        );
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[test]
+    fn rejected_client() {
+        let body = r#"{"error": "invalid_client", "error_description": "Invalid client secret"}"#;
+        let error = SpotifyError::from_response(StatusCode::BAD_REQUEST, body);
+        assert!(
+            matches!(&error, SpotifyError::BadCredentials(Some(body)) if body.status == 400 && body.message == "Invalid client secret"),
+            "{:?}",
+            error
+        );
+        let body = r#"{"error": {"status": 400, "message": "invalid id"}}"#;
+        let error = SpotifyError::from_response(StatusCode::BAD_REQUEST, body);
+        assert!(
+            matches!(
+                error,
+                SpotifyError::Http {
+                    status: StatusCode::BAD_REQUEST,
+                    ..
+                }
+            ),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn forbidden() {
+        let body = r#"{"error": {"status": 403, "message": "Forbidden"}}"#;
+        let error = SpotifyError::from_response(StatusCode::FORBIDDEN, body);
+        assert!(
+            matches!(
+                &error,
+                SpotifyError::Http {
+                    status: StatusCode::FORBIDDEN,
+                    body: Some(_)
+                }
+            ),
+            "{:?}",
+            error
+        );
+        assert_eq!(error.exit_code(), 7);
+    }
+
+    #[test]
+    fn not_found() {
+        let error = SpotifyError::from_response(StatusCode::NOT_FOUND, API_BODY);
+        assert!(
+            matches!(&error, SpotifyError::NotFound(Some(body)) if body.message == "Non existing id"),
+            "{:?}",
+            error
+        );
+        assert_eq!(error.exit_code(), 2);
+        assert_eq!(error.to_string(), "Not found: Non existing id (404)");
+    }
+
+    #[test]
+    fn rate_limited() {
+        let error = SpotifyError::from_response(StatusCode::TOO_MANY_REQUESTS, "");
+        assert!(
+            matches!(error, SpotifyError::RateLimited(None)),
+            "{:?}",
+            error
+        );
+        assert_eq!(error.exit_code(), 4);
+    }
+
+    #[test]
+    fn server_errors() {
+        for status in [
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_GATEWAY,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ] {
+            let error = SpotifyError::from_response(status, "<html>Upstream error</html>");
+            assert!(
+                matches!(&error, SpotifyError::Http { status: s, body: None } if *s == status),
+                "{:?}",
+                error
+            );
+            assert_eq!(error.exit_code(), 7);
+        }
+    }
+
+    #[test]
+    fn deserialize_path() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Artist {
+            #[allow(dead_code)]
+            popularity: u32,
+        }
+        let error =
+            from_json::<Vec<Artist>>(r#"[{"popularity": 1}, {"popularity": "high"}]"#).unwrap_err();
+        assert_eq!(error.exit_code(), 6);
+        assert!(error.to_string().contains("[1].popularity"), "{}", error);
+    }
+}
@@ -0,0 +1,233 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::{header::RETRY_AFTER, RequestBuilder, Response, StatusCode};
+use tokio::{sync::Mutex, time::Instant};
+
+use crate::{
+    config::{KOTO_MAX_RETRIES, KOTO_REQUESTS_PER_SECOND},
+    libs::{
+        cassette::{Cassette, CassetteMode},
+        error::SpotifyResult,
+    },
+};
+
+const DEFAULT_REQUESTS_PER_SECOND: f64 = 5.0;
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const BACKOFF_BASE_MS: u64 = 500;
+const BACKOFF_MAX_MS: u64 = 30_000;
+
+/// Send the requests to the spotify api while respecting the rate limit.
+///
+/// Requests are spaced to stay under the requests per second budget,
+/// 429 responses are retried after the `Retry-After` delay and
+/// server or connection errors are retried with an exponential backoff.
+pub struct RequestExecutor {
+    min_interval: Duration,
+    max_retries: u32,
+    last_request: Mutex<Option<Instant>>,
+    cassette: Option<Cassette>,
+}
+
+impl RequestExecutor {
+    pub fn new(requests_per_second: f64, max_retries: u32) -> Self {
+        let min_interval = match requests_per_second > 0.0 {
+            true => Duration::from_secs_f64(1.0 / requests_per_second),
+            false => Duration::ZERO,
+        };
+        Self {
+            min_interval,
+            max_retries,
+            last_request: Mutex::new(None),
+            cassette: None,
+        }
+    }
+
+    /// Record or replay the exchanges with the cassette
+    pub fn with_cassette(mut self, cassette: Option<Cassette>) -> Self {
+        self.cassette = cassette;
+        self
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.cassette
+            .as_ref()
+            .map(|cassette| cassette.mode() == CassetteMode::Replay)
+            .unwrap_or(false)
+    }
+
+    /// Create the executor with the budget set in the environment
+    pub fn from_env() -> SpotifyResult<Self> {
+        let requests_per_second = std::env::var(KOTO_REQUESTS_PER_SECOND)
+            .ok()
+            .and_then(|rps| rps.parse::<f64>().ok())
+            .unwrap_or(DEFAULT_REQUESTS_PER_SECOND);
+        let max_retries = std::env::var(KOTO_MAX_RETRIES)
+            .ok()
+            .and_then(|retries| retries.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_MAX_RETRIES);
+        let cassette = Cassette::from_env()?;
+        Ok(Self::new(requests_per_second, max_retries).with_cassette(cassette))
+    }
+
+    /// Wait until a request can be sent without exceeding the budget
+    async fn throttle(&self) {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(last) = *last_request {
+            let next = last + self.min_interval;
+            if next > Instant::now() {
+                tokio::time::sleep_until(next).await;
+            }
+        }
+        *last_request = Some(Instant::now());
+    }
+
+    fn backoff(attempt: u32) -> Duration {
+        let ceil = BACKOFF_BASE_MS
+            .saturating_mul(1 << attempt.min(16))
+            .min(BACKOFF_MAX_MS);
+        let jitter = rand::thread_rng().gen_range(0..=ceil / 2);
+        Duration::from_millis(ceil / 2 + jitter)
+    }
+
+    fn retry_after(response: &Response) -> Option<Duration> {
+        let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+        Self::parse_retry_after(value, Utc::now())
+    }
+
+    /// `Retry-After` given in seconds or as an http date, a past date is no delay
+    fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+        let value = value.trim();
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        let date = DateTime::parse_from_rfc2822(value).ok()?;
+        Some(
+            (date.with_timezone(&Utc) - now)
+                .to_std()
+                .unwrap_or(Duration::ZERO),
+        )
+    }
+
+    /// Send the request, or replay it from the cassette.
+    pub async fn execute(&self, rb: RequestBuilder) -> reqwest::Result<Response> {
+        let request = rb.try_clone().and_then(|rb| rb.build().ok());
+        let (cassette, request) = match (&self.cassette, request) {
+            (Some(cassette), Some(request)) => (cassette, request),
+            _ => return self.send(rb).await,
+        };
+        match cassette.mode() {
+            CassetteMode::Replay => Ok(cassette.replay(request.method(), request.url())),
+            CassetteMode::Record => {
+                let response = self.send(rb).await?;
+                cassette
+                    .record(request.method(), request.url(), response)
+                    .await
+            }
+        }
+    }
+
+    /// Send the request, retrying it as long as the failure is transient.
+    ///
+    /// The last response is returned if the retries are exhausted,
+    /// so the caller can still inspect the status
+    async fn send(&self, rb: RequestBuilder) -> reqwest::Result<Response> {
+        let mut attempt = 0;
+        let mut rb = rb;
+        loop {
+            let retry = rb.try_clone();
+            let () = self.throttle().await;
+            let result = rb.send().await;
+            let next = match retry {
+                Some(next) if attempt < self.max_retries => next,
+                _ => return result,
+            };
+            let delay = match &result {
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    Self::retry_after(response).unwrap_or_else(|| Self::backoff(attempt))
+                }
+                Ok(response) if response.status().is_server_error() => Self::backoff(attempt),
+                Ok(_) => return result,
+                Err(e) if e.is_connect() || e.is_timeout() || e.is_request() => {
+                    Self::backoff(attempt)
+                }
+                Err(_) => return result,
+            };
+            let () = tokio::time::sleep(delay).await;
+            attempt += 1;
+            rb = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::{RequestExecutor, BACKOFF_BASE_MS, BACKOFF_MAX_MS};
+
+    #[test]
+    fn backoff_bounds() {
+        for attempt in 0..40 {
+            let ceil = (BACKOFF_BASE_MS << attempt.min(16)).min(BACKOFF_MAX_MS);
+            for _ in 0..50 {
+                let delay = RequestExecutor::backoff(attempt);
+                assert!(delay >= Duration::from_millis(ceil / 2), "{:?}", delay);
+                assert!(delay <= Duration::from_millis(ceil), "{:?}", delay);
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_cap() {
+        assert!(RequestExecutor::backoff(0) <= Duration::from_millis(BACKOFF_BASE_MS));
+        assert!(RequestExecutor::backoff(6) <= Duration::from_millis(BACKOFF_MAX_MS));
+        assert!(RequestExecutor::backoff(6) >= Duration::from_millis(BACKOFF_MAX_MS / 2));
+        assert!(RequestExecutor::backoff(u32::MAX) <= Duration::from_millis(BACKOFF_MAX_MS));
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let now = Utc::now();
+        let parse = |value| RequestExecutor::parse_retry_after(value, now);
+        assert_eq!(parse("3"), Some(Duration::from_secs(3)));
+        assert_eq!(parse(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(parse("0"), Some(Duration::ZERO));
+        assert_eq!(parse("-1"), None);
+        assert_eq!(parse("soon"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn retry_after_date() {
+        let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 27, 30).unwrap();
+        let parse = |value| RequestExecutor::parse_retry_after(value, now);
+        assert_eq!(
+            parse("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::from_secs(30))
+        );
+        // Already passed
+        assert_eq!(parse("Wed, 21 Oct 2015 07:00:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse("Wed, 21 Oct 2015 25:00:00 GMT"), None);
+    }
+}
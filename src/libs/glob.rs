@@ -0,0 +1,270 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::{
+    fs::read_dir,
+    path::{Component, Path, PathBuf},
+};
+
+/// Whether the path contains a wildcard (`*`, `?` or `[...]`)
+pub fn is_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Match a file name against a pattern made of `*`, `?`, `[abc]`, `[a-z]` and `[!abc]`
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    matches_chars(&pattern, &name)
+}
+
+/// Iterative matching, on a mismatch only the last `*` is extended.
+/// It isn't linear: the worst case is O(p·n) for a pattern of p and a name of n characters,
+/// but it avoids the exponential time of a backtracking on every `*`
+fn matches_chars(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Pattern index following the last star and the name index it was tried at
+    let mut star = None;
+    while n < name.len() {
+        if pattern.get(p) == Some(&'*') {
+            star = Some((p + 1, n));
+            p += 1;
+            continue;
+        }
+        if let Some((true, len)) = match_one(&pattern[p..], name[n]) {
+            p += len;
+            n += 1;
+            continue;
+        }
+        match star {
+            Some((star_p, star_n)) => {
+                star = Some((star_p, star_n + 1));
+                p = star_p;
+                n = star_n + 1;
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Whether the first token of the pattern matches the character, along with the token length
+fn match_one(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    match pattern.split_first()? {
+        ('?', _) => Some((true, 1)),
+        ('[', rest) => match class_end(rest) {
+            Some(end) => Some((in_class(&rest[..end], c), end + 2)),
+            // An unclosed bracket is a regular character
+            None => Some((c == '[', 1)),
+        },
+        (p, _) => Some((*p == c, 1)),
+    }
+}
+
+/// Index of the closing bracket, a `]` right after the opening one is part of the class
+fn class_end(pattern: &[char]) -> Option<usize> {
+    let start = match pattern.first() {
+        Some('!') => 2,
+        _ => 1,
+    };
+    pattern
+        .iter()
+        .skip(start)
+        .position(|c| *c == ']')
+        .map(|index| index + start)
+}
+
+fn in_class(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.split_first() {
+        Some(('!', class)) => (true, class),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut index = 0;
+    while index < class.len() {
+        if index + 2 < class.len() && class[index + 1] == '-' {
+            found |= class[index] <= c && c <= class[index + 2];
+            index += 3;
+        } else {
+            found |= class[index] == c;
+            index += 1;
+        }
+    }
+    found != negated
+}
+
+/// Existing paths matching the pattern, `**` matches any number of directories
+pub fn expand(pattern: &str) -> Vec<PathBuf> {
+    let path = Path::new(pattern);
+    let mut candidates = vec![PathBuf::new()];
+    for component in path.components() {
+        let part = match component {
+            Component::Normal(part) => part.to_string_lossy(),
+            other => {
+                candidates
+                    .iter_mut()
+                    .for_each(|candidate| candidate.push(other.as_os_str()));
+                continue;
+            }
+        };
+        candidates = match part.as_ref() {
+            "**" => candidates
+                .into_iter()
+                .flat_map(|candidate| {
+                    let mut dirs = vec![];
+                    sub_directories(candidate, &mut dirs);
+                    dirs
+                })
+                .collect(),
+            part if is_pattern(part) => candidates
+                .into_iter()
+                .flat_map(|candidate| matching_entries(&candidate, part))
+                .collect(),
+            part => candidates
+                .into_iter()
+                .map(|candidate| candidate.join(part))
+                .filter(|candidate| candidate.exists())
+                .collect(),
+        };
+    }
+    candidates.sort();
+    candidates
+}
+
+fn dir_or_current(dir: &Path) -> &Path {
+    match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir,
+    }
+}
+
+/// Entries of dir matching the pattern, hidden entries only match a pattern starting with a dot
+fn matching_entries(dir: &Path, pattern: &str) -> Vec<PathBuf> {
+    let entries = match read_dir(dir_or_current(dir)) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            (pattern.starts_with('.') || !name.starts_with('.')) && matches(pattern, &name)
+        })
+        .map(|entry| dir.join(entry.file_name()))
+        .collect()
+}
+
+/// dir and all its sub directories
+fn sub_directories(dir: PathBuf, dirs: &mut Vec<PathBuf>) {
+    if let Ok(entries) = read_dir(dir_or_current(&dir)) {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            // The symbolic links aren't followed, they could loop
+            let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+            if !hidden && is_dir {
+                sub_directories(dir.join(entry.file_name()), dirs)
+            }
+        }
+    }
+    dirs.push(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{expand, is_pattern, matches};
+
+    #[test]
+    fn star() {
+        assert!(matches("*", ""));
+        assert!(matches("*.flac", "01 - One More Time.flac"));
+        assert!(matches("a*b*c", "aXXbYYc"));
+        assert!(matches("a*b", "abab"));
+        assert!(matches("**", "song.mp3"));
+        assert!(!matches("*.flac", "song.flac.bak"));
+        assert!(!matches("a*b*c", "aXXbYY"));
+    }
+
+    #[test]
+    fn many_stars() {
+        // Exponential with a backtracking on every star, O(p·n) here
+        let name = "a".repeat(100);
+        assert!(!matches(&format!("{}b", "a*".repeat(20)), &name));
+        assert!(matches(&"a*".repeat(20), &name));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(matches("??.mp3", "01.mp3"));
+        assert!(!matches("??.mp3", "1.mp3"));
+        assert!(!matches("?", ""));
+        assert!(matches("é?", "éa"));
+    }
+
+    #[test]
+    fn class() {
+        assert!(matches("[a-z].ogg", "b.ogg"));
+        assert!(!matches("[a-z].ogg", "B.ogg"));
+        assert!(matches("[abc]", "c"));
+        assert!(matches("[0-9][0-9] *", "07 Voyager"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[a-]", "-"));
+    }
+
+    #[test]
+    fn negated_class() {
+        assert!(matches("[!x].wav", "a.wav"));
+        assert!(!matches("[!x].wav", "x.wav"));
+        assert!(!matches("[!a-z]", "m"));
+        assert!(matches("[!]]", "a"));
+    }
+
+    #[test]
+    fn unclosed_bracket() {
+        assert!(is_pattern("[live"));
+        assert!(matches("[live*", "[live] Alive 1997.flac"));
+        assert!(!matches("[live*", "live.flac"));
+        assert!(matches("[!", "[!"));
+    }
+
+    #[test]
+    fn double_star() {
+        let root = std::env::temp_dir().join(format!("koto-glob-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let () = fs::create_dir_all(root.join("album/cd1")).unwrap();
+        let () = fs::create_dir_all(root.join(".hidden")).unwrap();
+        for file in [
+            "a.flac",
+            "album/b.flac",
+            "album/cd1/c.flac",
+            "album/c.mp3",
+            ".hidden/d.flac",
+        ] {
+            let () = fs::write(root.join(file), b"").unwrap();
+        }
+        #[cfg(unix)]
+        let () = std::os::unix::fs::symlink(&root, root.join("album/loop")).unwrap();
+        let found = expand(&format!("{}/**/*.flac", root.display()));
+        let expected = ["a.flac", "album/b.flac", "album/cd1/c.flac"]
+            .iter()
+            .map(|file| root.join(file))
+            .collect::<Vec<PathBuf>>();
+        let _ = fs::remove_dir_all(&root);
+        assert_eq!(found, expected);
+    }
+}
@@ -0,0 +1,160 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::path::{Component, Path, PathBuf};
+
+use serde_json::{Map, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// Maximum number of ids in a request
+pub const MAX_IDS: usize = 100;
+
+/// Http server answering the spotify api requests with json fixtures.
+///
+/// A request on `/v1/artists/<id>` is answered with `<fixtures>/v1/artists/<id>.json`.
+/// Searches are resolved with their type: `/v1/search?type=album` is answered
+/// with `<fixtures>/v1/search/album.json`.
+/// The token request `/api/token` is answered with `<fixtures>/api/token.json`.
+/// Without a fixture, `/v1/audio-features?ids=<a>,<b>` is answered with
+/// `{"audio_features": [<a>, <b>]}` built from the fixtures of each id,
+/// and more than [MAX_IDS] ids are rejected as the api does
+pub struct MockServer {
+    fixtures: PathBuf,
+    listener: TcpListener,
+}
+
+impl MockServer {
+    pub async fn bind(fixtures: PathBuf, address: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address).await?;
+        Ok(Self { fixtures, listener })
+    }
+
+    /// Url to use as KOTO_ACCOUNTS_URL, the api is served under `/v1`
+    pub fn base_url(&self) -> std::io::Result<String> {
+        let address = self.listener.local_addr()?;
+        Ok(format!("http://{}", address))
+    }
+
+    pub async fn run(self) -> std::io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let fixtures = self.fixtures.clone();
+            tokio::spawn(async move {
+                let _ = Self::handle(&fixtures, stream).await;
+            });
+        }
+    }
+
+    async fn read_request(stream: &mut TcpStream) -> std::io::Result<String> {
+        let mut request = vec![];
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = stream.read(&mut buffer).await?;
+            request.extend_from_slice(&buffer[..read]);
+            let header_end = request.windows(4).position(|w| w == b"\r\n\r\n");
+            if read == 0 || header_end.is_some() {
+                break;
+            }
+        }
+        Ok(String::from_utf8_lossy(&request).to_string())
+    }
+
+    /// Fixture file answering the request target
+    fn fixture_path(fixtures: &Path, target: &str) -> Option<PathBuf> {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let relative = Path::new(path.trim_start_matches('/'));
+        let is_safe = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if !is_safe || relative.as_os_str().is_empty() {
+            return None;
+        }
+        let search_type = query
+            .split('&')
+            .find_map(|parameter| parameter.strip_prefix("type="));
+        let file = match search_type {
+            Some(search_type) => relative
+                .join(search_type.replace("%2C", ","))
+                .with_extension("json"),
+            None => relative.with_extension("json"),
+        };
+        Some(fixtures.join(file))
+    }
+
+    /// Ids requested with the `ids` parameter
+    fn requested_ids(target: &str) -> Option<Vec<&str>> {
+        let (_, query) = target.split_once('?')?;
+        let ids = query
+            .split('&')
+            .find_map(|parameter| parameter.strip_prefix("ids="))?;
+        Some(ids.split("%2C").flat_map(|ids| ids.split(',')).collect())
+    }
+
+    /// Response of a several ids request assembled from the fixtures of each id
+    fn assemble(fixtures: &Path, target: &str, ids: &[&str]) -> Option<Vec<u8>> {
+        let path = target.split_once('?').map_or(target, |(path, _)| path);
+        let ressource = path.trim_end_matches('/').rsplit('/').next()?;
+        let items = ids
+            .iter()
+            .map(|id| {
+                Self::fixture_path(fixtures, &format!("{}/{}", path, id))
+                    .and_then(|path| std::fs::read(path).ok())
+                    .and_then(|body| serde_json::from_slice(&body).ok())
+                    .unwrap_or(Value::Null)
+            })
+            .collect::<Vec<_>>();
+        let mut body = Map::new();
+        let _ = body.insert(ressource.replace('-', "_"), Value::Array(items));
+        serde_json::to_vec(&body).ok()
+    }
+
+    async fn handle(fixtures: &Path, mut stream: TcpStream) -> std::io::Result<()> {
+        let request = Self::read_request(&mut stream).await?;
+        let target = request
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .unwrap_or("/");
+        let ids = Self::requested_ids(target);
+        let fixture = Self::fixture_path(fixtures, target)
+            .and_then(|path| std::fs::read(path).ok())
+            .or_else(|| Self::assemble(fixtures, target, ids.as_deref()?));
+        let (status, body) = match fixture {
+            _ if ids.as_ref().is_some_and(|ids| ids.len() > MAX_IDS) => (
+                "400 Bad Request",
+                br#"{"error": {"status": 400, "message": "Too many ids requested"}}"#.to_vec(),
+            ),
+            Some(body) => ("200 OK", body),
+            None => (
+                "404 Not Found",
+                br#"{"error": {"status": 404, "message": "No fixture for this ressource"}}"#
+                    .to_vec(),
+            ),
+        };
+        let header = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            body.len()
+        );
+        stream.write_all(header.as_bytes()).await?;
+        stream.write_all(&body).await?;
+        stream.shutdown().await
+    }
+}
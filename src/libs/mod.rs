@@ -15,5 +15,19 @@
 //                                                                                            //
 // /////////////////////////////////////////////////////////////////////////////////////////////
 
+pub mod cache;
+pub mod cassette;
+pub mod error;
+pub mod executor;
+pub mod glob;
+pub mod mock;
+pub mod output;
+pub mod paging;
+pub mod picker;
+pub mod query;
+pub mod related_graph;
+pub mod release_date;
 pub mod spotify;
+pub mod spotify_id;
+pub mod tags;
 pub mod util;
@@ -0,0 +1,584 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use clap::ArgEnum;
+use serde::Serialize;
+
+use super::spotify::{
+    Album, AlbumItems, Artist, AudioFeatures, SpotifySearchResultItem, Track, TrackAlbum,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl OutputFormat {
+    pub fn is_text(&self) -> bool {
+        *self == Self::Text
+    }
+}
+
+/// Entity printed as a row with the csv and tsv formats
+pub trait Record: Serialize {
+    const HEADERS: &'static [&'static str];
+    fn row(&self) -> Vec<String>;
+}
+
+/// Implement [Record] from a single list of `header => value` columns,
+/// so that the headers and the rows can't get out of step
+macro_rules! record {
+    ($record:ty, |$this:ident| { $($header:literal => $value:expr),* $(,)? }) => {
+        impl $crate::libs::output::Record for $record {
+            const HEADERS: &'static [&'static str] = &[$($header),*];
+
+            fn row(&self) -> Vec<String> {
+                let $this = self;
+                vec![$($value),*]
+            }
+        }
+    };
+}
+
+pub(crate) use record;
+
+fn csv_field(field: &str) -> String {
+    match field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+fn tsv_field(field: &str) -> String {
+    field.replace(|c| c == '\t' || c == '\n' || c == '\r', " ")
+}
+
+fn optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(T::to_string).unwrap_or_default()
+}
+
+/// Records in the given format, text is formatted as tsv
+pub fn format_records<R: Record>(format: OutputFormat, records: &[R]) -> String {
+    let (separator, escape): (&str, fn(&str) -> String) = match format {
+        OutputFormat::Json => {
+            return serde_json::to_string_pretty(records).unwrap_or_else(|e| e.to_string())
+        }
+        OutputFormat::Csv => (",", csv_field),
+        OutputFormat::Text | OutputFormat::Tsv => ("\t", tsv_field),
+    };
+    let mut lines = vec![R::HEADERS
+        .iter()
+        .map(|header| escape(header))
+        .collect::<Vec<_>>()
+        .join(separator)];
+    for record in records {
+        lines.push(
+            record
+                .row()
+                .iter()
+                .map(|field| escape(field))
+                .collect::<Vec<_>>()
+                .join(separator),
+        )
+    }
+    lines.join("\n")
+}
+
+/// Print the records with the format set by --output-format
+pub fn print_records<R: Record>(format: OutputFormat, records: &[R]) {
+    println!("{}", format_records(format, records))
+}
+
+pub fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => println!("{}", e),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtistRecord {
+    pub id: String,
+    pub name: String,
+    pub genres: Vec<String>,
+    pub popularity: i32,
+    pub followers: Option<u64>,
+    pub uri: String,
+}
+
+impl From<&Artist> for ArtistRecord {
+    fn from(artist: &Artist) -> Self {
+        Self {
+            id: artist.id.clone(),
+            name: artist.name.clone(),
+            genres: artist.genres.clone(),
+            popularity: artist.popularity,
+            followers: artist
+                .followers
+                .get("total")
+                .and_then(|total| total.as_u64()),
+            uri: artist.uri.clone(),
+        }
+    }
+}
+
+record! {
+    ArtistRecord, |record| {
+        "id" => record.id.clone(),
+        "name" => record.name.clone(),
+        "genres" => record.genres.join(";"),
+        "popularity" => record.popularity.to_string(),
+        "followers" => optional(&record.followers),
+        "uri" => record.uri.clone(),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlbumRecord {
+    pub id: String,
+    pub name: String,
+    pub album_type: String,
+    pub album_group: Option<String>,
+    pub artists: Vec<String>,
+    pub release_date: String,
+    pub total_tracks: u32,
+    pub uri: String,
+}
+
+impl From<&AlbumItems> for AlbumRecord {
+    fn from(album: &AlbumItems) -> Self {
+        Self {
+            id: album.id.clone(),
+            name: album.name.clone(),
+            album_type: album.album_type.clone(),
+            album_group: Some(album.album_group.clone()).filter(|group| !group.is_empty()),
+            artists: album
+                .artists
+                .iter()
+                .filter_map(|artist| artist.get("name")?.as_str().map(String::from))
+                .collect(),
+            release_date: album.release_date.to_string(),
+            total_tracks: album.total_tracks,
+            uri: album.uri.clone(),
+        }
+    }
+}
+
+record! {
+    AlbumRecord, |record| {
+        "id" => record.id.clone(),
+        "name" => record.name.clone(),
+        "album_type" => record.album_type.clone(),
+        "album_group" => optional(&record.album_group),
+        "artists" => record.artists.join(";"),
+        "release_date" => record.release_date.clone(),
+        "total_tracks" => record.total_tracks.to_string(),
+        "uri" => record.uri.clone(),
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TrackRecord {
+    pub id: String,
+    pub name: String,
+    pub artists: Vec<String>,
+    pub album: String,
+    pub album_id: String,
+    pub release_date: Option<String>,
+    pub disc_number: u16,
+    pub track_number: u16,
+    pub explicit: bool,
+    pub isrc: Option<String>,
+    pub duration_ms: u64,
+    pub popularity: Option<u32>,
+    pub uri: String,
+    pub tempo: Option<f64>,
+    pub key: Option<String>,
+    pub energy: Option<f64>,
+    pub danceability: Option<f64>,
+    pub loudness: Option<f64>,
+}
+
+impl TrackRecord {
+    pub fn with_features(mut self, features: &AudioFeatures) -> Self {
+        self.tempo = Some(features.tempo);
+        self.key = features.key_name();
+        self.energy = Some(features.energy);
+        self.danceability = Some(features.danceability);
+        self.loudness = Some(features.loudness);
+        self
+    }
+
+    /// Track of an album, which doesn't embed its album
+    pub fn from_album_track(track: &TrackAlbum, album: &Album) -> Self {
+        Self {
+            id: track.id.clone(),
+            name: track.name.clone(),
+            artists: track
+                .artists
+                .iter()
+                .map(|artist| artist.name.clone())
+                .collect(),
+            album: album.name.clone(),
+            album_id: album.id.clone(),
+            release_date: Some(album.release_date.to_string()),
+            disc_number: track.disc_number,
+            track_number: track.track_number,
+            explicit: track.explicit,
+            duration_ms: track.duration_ms,
+            uri: track.uri.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&Track> for TrackRecord {
+    fn from(track: &Track) -> Self {
+        Self {
+            id: track.id.clone(),
+            name: track.name.clone(),
+            artists: track
+                .artists
+                .iter()
+                .map(|artist| artist.name.clone())
+                .collect(),
+            album: track.album.name.clone(),
+            album_id: track.album.id.clone(),
+            release_date: Some(track.album.release_date.to_string()),
+            disc_number: track.disc_number,
+            track_number: track.track_number,
+            explicit: track.explicit,
+            isrc: track.isrc().cloned(),
+            duration_ms: track.duration_ms,
+            popularity: Some(track.popularity),
+            uri: track.uri.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+record! {
+    TrackRecord, |record| {
+        "id" => record.id.clone(),
+        "name" => record.name.clone(),
+        "artists" => record.artists.join(";"),
+        "album" => record.album.clone(),
+        "album_id" => record.album_id.clone(),
+        "release_date" => optional(&record.release_date),
+        "disc_number" => record.disc_number.to_string(),
+        "track_number" => record.track_number.to_string(),
+        "explicit" => record.explicit.to_string(),
+        "isrc" => optional(&record.isrc),
+        "duration_ms" => record.duration_ms.to_string(),
+        "popularity" => optional(&record.popularity),
+        "uri" => record.uri.clone(),
+        "tempo" => optional(&record.tempo),
+        "key" => optional(&record.key),
+        "energy" => optional(&record.energy),
+        "danceability" => optional(&record.danceability),
+        "loudness" => optional(&record.loudness),
+    }
+}
+
+/// Album with its details, only printed as json. The csv and tsv formats print its tracks
+#[derive(Debug, Serialize)]
+pub struct AlbumDetailsRecord {
+    pub id: String,
+    pub name: String,
+    pub album_type: String,
+    pub artists: Vec<String>,
+    pub release_date: String,
+    pub label: Option<String>,
+    pub upc: Option<String>,
+    pub genres: Vec<String>,
+    pub copyrights: Vec<String>,
+    pub runtime_ms: u64,
+    pub total_tracks: u32,
+    pub uri: String,
+    pub tracks: Vec<TrackRecord>,
+}
+
+impl From<&Album> for AlbumDetailsRecord {
+    fn from(album: &Album) -> Self {
+        Self {
+            id: album.id.clone(),
+            name: album.name.clone(),
+            album_type: album.album_type.clone(),
+            artists: album
+                .artists
+                .iter()
+                .map(|artist| artist.name.clone())
+                .collect(),
+            release_date: album.release_date.to_string(),
+            label: album.label.clone().flatten(),
+            upc: album
+                .external_ids
+                .as_ref()
+                .and_then(|ids| ids.as_ref())
+                .and_then(|ids| ids.get("upc").cloned()),
+            genres: album.genres.clone().flatten().unwrap_or_default(),
+            copyrights: album
+                .copyrights
+                .as_ref()
+                .and_then(|copyrights| copyrights.as_ref())
+                .map(|copyrights| {
+                    copyrights
+                        .iter()
+                        .map(|copyright| copyright.text.clone())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            runtime_ms: album.runtime_ms(),
+            total_tracks: album.total_tracks,
+            uri: album.uri.clone(),
+            tracks: album
+                .tracks
+                .items
+                .iter()
+                .map(|track| TrackRecord::from_album_track(track, album))
+                .collect(),
+        }
+    }
+}
+
+/// Print albums with the format set by --output-format
+pub fn print_albums(format: OutputFormat, albums: &[Album]) {
+    let details = albums
+        .iter()
+        .map(AlbumDetailsRecord::from)
+        .collect::<Vec<_>>();
+    match format {
+        OutputFormat::Json => print_json(&details),
+        _ => print_records(
+            format,
+            &details
+                .into_iter()
+                .flat_map(|details| details.tracks)
+                .collect::<Vec<_>>(),
+        ),
+    }
+}
+
+/// Search result of any ressource type
+#[derive(Debug, Serialize)]
+pub struct SearchRecord {
+    #[serde(rename = "type")]
+    pub r_type: String,
+    pub id: String,
+    pub name: String,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub release_date: Option<String>,
+    pub genres: Vec<String>,
+    pub uri: String,
+}
+
+impl From<&SpotifySearchResultItem> for SearchRecord {
+    fn from(item: &SpotifySearchResultItem) -> Self {
+        match item {
+            SpotifySearchResultItem::Track {
+                album,
+                artists,
+                id,
+                name,
+                uri,
+                ..
+            } => Self {
+                r_type: "track".into(),
+                id: id.clone(),
+                name: name.clone(),
+                artists: artists.iter().map(|artist| artist.name.clone()).collect(),
+                album: Some(album.name.clone()),
+                release_date: Some(album.release_date.to_string()),
+                genres: vec![],
+                uri: uri.clone(),
+            },
+            SpotifySearchResultItem::Artist {
+                genres,
+                id,
+                name,
+                uri,
+                ..
+            } => Self {
+                r_type: "artist".into(),
+                id: id.clone(),
+                name: name.clone(),
+                artists: vec![],
+                album: None,
+                release_date: None,
+                genres: genres.clone(),
+                uri: uri.clone(),
+            },
+            SpotifySearchResultItem::Album {
+                artists,
+                id,
+                name,
+                release_date,
+                uri,
+                ..
+            } => Self {
+                r_type: "album".into(),
+                id: id.clone(),
+                name: name.clone(),
+                artists: artists.iter().map(|artist| artist.name.clone()).collect(),
+                album: None,
+                release_date: Some(release_date.to_string()),
+                genres: vec![],
+                uri: uri.clone(),
+            },
+        }
+    }
+}
+
+record! {
+    SearchRecord, |record| {
+        "type" => record.r_type.clone(),
+        "id" => record.id.clone(),
+        "name" => record.name.clone(),
+        "artists" => record.artists.join(";"),
+        "album" => optional(&record.album),
+        "release_date" => optional(&record.release_date),
+        "genres" => record.genres.join(";"),
+        "uri" => record.uri.clone(),
+    }
+}
+
+/// Track of a cue sheet
+#[derive(Debug, Serialize)]
+pub struct CueTrackRecord {
+    pub number: u32,
+    pub title: String,
+    pub performer: String,
+    pub start_ms: u64,
+    pub duration_ms: u64,
+}
+
+record! {
+    CueTrackRecord, |record| {
+        "number" => record.number.to_string(),
+        "title" => record.title.clone(),
+        "performer" => record.performer.clone(),
+        "start_ms" => record.start_ms.to_string(),
+        "duration_ms" => record.duration_ms.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_record<R: Record>(record: &R) {
+        let row = record.row();
+        assert_eq!(R::HEADERS.len(), row.len());
+        let json = serde_json::to_value(record).unwrap();
+        let fields = json.as_object().unwrap();
+        assert_eq!(R::HEADERS.len(), fields.len());
+        for header in R::HEADERS {
+            assert!(fields.contains_key(*header), "{}", header)
+        }
+    }
+
+    #[test]
+    fn csv_escaping() {
+        assert_eq!(csv_field("Discovery"), "Discovery");
+        assert_eq!(
+            csv_field("Daft Punk, Romanthony"),
+            "\"Daft Punk, Romanthony\""
+        );
+        assert_eq!(csv_field("The \"Hits\""), "\"The \"\"Hits\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(csv_field("carriage\rreturn"), "\"carriage\rreturn\"");
+        assert_eq!(csv_field("tab\there"), "tab\there");
+    }
+
+    #[test]
+    fn tsv_escaping() {
+        assert_eq!(tsv_field("Discovery"), "Discovery");
+        assert_eq!(tsv_field("tab\there"), "tab here");
+        assert_eq!(tsv_field("line\nbreak\r"), "line break ");
+        assert_eq!(tsv_field("a, \"b\""), "a, \"b\"");
+    }
+
+    #[test]
+    fn headers_match_rows() {
+        check_record(&TrackRecord::default());
+        check_record(&ArtistRecord {
+            id: String::new(),
+            name: String::new(),
+            genres: vec![],
+            popularity: 0,
+            followers: None,
+            uri: String::new(),
+        });
+        check_record(&AlbumRecord {
+            id: String::new(),
+            name: String::new(),
+            album_type: String::new(),
+            album_group: None,
+            artists: vec![],
+            release_date: String::new(),
+            total_tracks: 0,
+            uri: String::new(),
+        });
+        check_record(&SearchRecord {
+            r_type: String::new(),
+            id: String::new(),
+            name: String::new(),
+            artists: vec![],
+            album: None,
+            release_date: None,
+            genres: vec![],
+            uri: String::new(),
+        });
+        check_record(&CueTrackRecord {
+            number: 1,
+            title: String::new(),
+            performer: String::new(),
+            start_ms: 0,
+            duration_ms: 0,
+        });
+    }
+
+    #[test]
+    fn escaped_records() {
+        let record = CueTrackRecord {
+            number: 1,
+            title: "One More Time, \"Radio Edit\"".to_string(),
+            performer: "Daft\tPunk".to_string(),
+            start_ms: 0,
+            duration_ms: 320357,
+        };
+        assert_eq!(
+            format_records(OutputFormat::Csv, &[record]).lines().nth(1),
+            Some("1,\"One More Time, \"\"Radio Edit\"\"\",Daft\tPunk,0,320357")
+        );
+        let record = CueTrackRecord {
+            number: 1,
+            title: "One More Time".to_string(),
+            performer: "Daft\tPunk".to_string(),
+            start_ms: 0,
+            duration_ms: 320357,
+        };
+        assert_eq!(
+            format_records(OutputFormat::Tsv, &[record]),
+            "number\ttitle\tperformer\tstart_ms\tduration_ms\n1\tOne More Time\tDaft Punk\t0\t320357"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::collections::VecDeque;
+
+use reqwest::RequestBuilder;
+use serde::de::DeserializeOwned;
+
+use super::{
+    error::SpotifyResult,
+    spotify::{Spotify, SpotifyRessourceType},
+};
+
+/// A page of a paginated spotify ressource
+pub trait Page: DeserializeOwned {
+    type Item;
+
+    /// Url of the next page
+    fn next_page(&self) -> Option<String>;
+    fn into_items(self) -> Vec<Self::Item>;
+}
+
+/// Walk through the pages of a ressource by following their `next` url.
+///
+/// Pages are only fetched when the items of the previous one have been consumed
+pub struct Paginator<'a, P: Page> {
+    spotify: &'a Spotify,
+    request: Option<RequestBuilder>,
+    /// Cache key of the first page
+    cache: Option<(SpotifyRessourceType, String)>,
+    items: VecDeque<P::Item>,
+    max_items: Option<usize>,
+    yielded: usize,
+}
+
+impl<'a, P: Page> Paginator<'a, P> {
+    /// Create the paginator from the request of the first page
+    pub(crate) fn new(spotify: &'a Spotify, request: RequestBuilder) -> Self {
+        Self {
+            spotify,
+            request: Some(request),
+            cache: None,
+            items: VecDeque::new(),
+            max_items: None,
+            yielded: 0,
+        }
+    }
+
+    /// Create the paginator with a first page looked up in the cache
+    pub(crate) fn cached(
+        spotify: &'a Spotify,
+        r_type: SpotifyRessourceType,
+        id: &str,
+        request: RequestBuilder,
+    ) -> Self {
+        let mut paginator = Self::new(spotify, request);
+        paginator.cache = Some((r_type, id.to_string()));
+        paginator
+    }
+
+    /// Stop after `max_items` items
+    pub fn max_items(mut self, max_items: Option<usize>) -> Self {
+        self.max_items = max_items;
+        self
+    }
+
+    pub async fn next(&mut self) -> Option<SpotifyResult<P::Item>> {
+        if self
+            .max_items
+            .map(|max| self.yielded >= max)
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        while self.items.is_empty() {
+            let request = self.request.take()?;
+            let page = match self.cache.take() {
+                Some((r_type, id)) => self.spotify.get_cached::<P>(&r_type, &id, request).await,
+                None => self.spotify.get::<P>(request).await,
+            };
+            let page = match page {
+                Ok(page) => page,
+                Err(e) => return Some(Err(e)),
+            };
+            self.request = page.next_page().map(|url| self.spotify.page_request(url));
+            self.items.extend(page.into_items());
+        }
+        self.yielded += 1;
+        self.items.pop_front().map(Ok)
+    }
+
+    /// Fetch the remaining items
+    pub async fn collect(mut self) -> SpotifyResult<Vec<P::Item>> {
+        let mut items = vec![];
+        while let Some(item) = self.next().await {
+            items.push(item?);
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde::Deserialize;
+
+    use super::{Page, Paginator};
+    use crate::libs::{
+        cache::ResponseCache,
+        error::SpotifyError,
+        executor::RequestExecutor,
+        mock::MockServer,
+        spotify::{Spotify, Token},
+    };
+
+    #[derive(Deserialize)]
+    struct Numbers {
+        items: Vec<u32>,
+        next: Option<String>,
+    }
+
+    impl Page for Numbers {
+        type Item = u32;
+
+        fn next_page(&self) -> Option<String> {
+            self.next.clone()
+        }
+
+        fn into_items(self) -> Vec<Self::Item> {
+            self.items
+        }
+    }
+
+    /// Mock server answering the pages, each page links to the next one
+    /// and the last one links to a missing page if `dangling` is set
+    async fn serve(test: &str, pages: &[&[u32]], dangling: bool) -> String {
+        let fixtures = std::env::temp_dir().join("koto-paging").join(format!(
+            "{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&fixtures);
+        let () = std::fs::create_dir_all(fixtures.join("v1/numbers")).unwrap();
+        let server = MockServer::bind(PathBuf::from(&fixtures), "127.0.0.1:0")
+            .await
+            .unwrap();
+        let base_url = server.base_url().unwrap();
+        let page_url = |index: usize| format!("{}/v1/numbers/{}", base_url, index);
+        for (index, items) in pages.iter().enumerate() {
+            let next = match index + 1 < pages.len() || dangling {
+                true => Some(page_url(index + 1)),
+                false => None,
+            };
+            let page = serde_json::json!({ "items": items, "next": next });
+            let path = fixtures.join(format!("v1/numbers/{}.json", index));
+            let () = std::fs::write(path, page.to_string()).unwrap();
+        }
+        // Detached, the server is stopped with the runtime of the test
+        drop(tokio::spawn(server.run()));
+        page_url(0)
+    }
+
+    fn spotify() -> Spotify {
+        Spotify::new(
+            Some(Token::replay()),
+            RequestExecutor::new(0.0, 0),
+            ResponseCache::new(false),
+        )
+    }
+
+    #[tokio::test]
+    async fn several_pages() {
+        let url = serve("several_pages", &[&[1, 2], &[3, 4], &[5]], false).await;
+        let spotify = spotify();
+        let paginator = Paginator::<Numbers>::new(&spotify, spotify.page_request(url));
+        assert_eq!(paginator.collect().await.unwrap(), [1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn empty_pages() {
+        let url = serve("empty_pages", &[&[], &[1], &[]], false).await;
+        let spotify = spotify();
+        let paginator = Paginator::<Numbers>::new(&spotify, spotify.page_request(url));
+        assert_eq!(paginator.collect().await.unwrap(), [1]);
+    }
+
+    #[tokio::test]
+    async fn max_items() {
+        let url = serve("max_items", &[&[1, 2], &[3, 4], &[5]], false).await;
+        let spotify = spotify();
+        let paginator =
+            Paginator::<Numbers>::new(&spotify, spotify.page_request(url)).max_items(Some(3));
+        assert_eq!(paginator.collect().await.unwrap(), [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn max_items_stops_fetching() {
+        let url = serve("max_items_stops_fetching", &[&[1, 2], &[3, 4]], true).await;
+        let spotify = spotify();
+        // The third page would fail, it's never requested
+        let paginator = Paginator::<Numbers>::new(&spotify, spotify.page_request(url.clone()))
+            .max_items(Some(4));
+        assert_eq!(paginator.collect().await.unwrap(), [1, 2, 3, 4]);
+        let mut paginator = Paginator::<Numbers>::new(&spotify, spotify.page_request(url));
+        for expected in 1..=4 {
+            assert_eq!(paginator.next().await.unwrap().unwrap(), expected);
+        }
+        assert!(matches!(
+            paginator.next().await,
+            Some(Err(SpotifyError::NotFound(_)))
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::io::{stderr, stdin, IsTerminal, Write};
+
+use viuer::Config;
+
+use super::{
+    error::{SpotifyError, SpotifyResult},
+    spotify::{Spotify, SpotifySearchResultItem, SpotifySearchType},
+    util,
+};
+
+/// Number of search results proposed to the user
+pub const PICKER_CANDIDATES: u8 = 5;
+
+fn readline(prompt: &str) -> Option<String> {
+    let () = eprint!("{}", prompt);
+    let _ = stderr().flush();
+    let mut buffer = String::new();
+    match stdin().read_line(&mut buffer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(buffer.trim().to_string()),
+    }
+}
+
+/// Let the user choose one of the search results.
+///
+/// The first result is taken without asking if `first` is set, if there is a single result
+/// or if the standard input isn't a terminal. Returns [None] if there is no result or if the user quits
+pub async fn pick(
+    items: Vec<SpotifySearchResultItem>,
+    first: bool,
+    graphic: bool,
+) -> Option<SpotifySearchResultItem> {
+    if first || items.len() <= 1 || !stdin().is_terminal() {
+        return items.into_iter().next();
+    }
+    for (index, item) in items.iter().enumerate() {
+        if graphic {
+            if let Some(image) = item.dynamic_image().await {
+                util::show_image_config(
+                    &image,
+                    &Config {
+                        absolute_offset: false,
+                        width: Some(20),
+                        height: None,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        eprintln!("{:>3}) {}", index + 1, item.candidate_format());
+    }
+    let prompt = format!(
+        "Choose a result [1-{}] (q to quit, default 1) : ",
+        items.len()
+    );
+    loop {
+        let answer = match readline(&prompt) {
+            None => return items.into_iter().next(),
+            Some(answer) => answer,
+        };
+        if answer.eq_ignore_ascii_case("q") {
+            return None;
+        }
+        if answer.is_empty() {
+            return items.into_iter().next();
+        }
+        match answer.parse::<usize>() {
+            Ok(choice) if (1..=items.len()).contains(&choice) => {
+                return items.into_iter().nth(choice - 1)
+            }
+            _ => eprintln!("Invalid choice : {}", answer),
+        }
+    }
+}
+
+/// Search a ressource and returns the id of the result chosen with [pick]
+///
+/// Fails with [SpotifyError::Cancelled] if the user quits the picker
+pub async fn search_and_pick(
+    spotify: &Spotify,
+    query: &str,
+    item_type: SpotifySearchType,
+    first: bool,
+    graphic: bool,
+) -> SpotifyResult<String> {
+    let limit = if first { 1 } else { PICKER_CANDIDATES };
+    let items = spotify.search_candidates(query, item_type, limit).await?;
+    if items.is_empty() {
+        return Err(SpotifyError::NotFound(None));
+    }
+    pick(items, first, graphic)
+        .await
+        .map(|item| item.id().to_string())
+        .ok_or(SpotifyError::Cancelled)
+}
@@ -0,0 +1,313 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::{fmt::Display, process::exit, str::FromStr};
+
+use clap::ArgEnum;
+
+#[derive(Debug)]
+pub struct QueryError(String);
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl QueryError {
+    pub fn exit(&self) -> ! {
+        eprintln!("{}", self);
+        exit(1)
+    }
+}
+
+/// Album tags accepted by the spotify search
+#[derive(Debug, Clone, Copy, ArgEnum)]
+pub enum SearchTag {
+    /// Albums released in the past two weeks
+    New,
+    /// Albums with the lowest 10% popularity
+    Hipster,
+}
+
+impl Display for SearchTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SearchTag::New => "new",
+            SearchTag::Hipster => "hipster",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A single year (`1997`) or an inclusive range of years (`1990-1999`)
+#[derive(Debug, Clone, Copy)]
+pub struct YearRange {
+    start: u16,
+    end: Option<u16>,
+}
+
+impl FromStr for YearRange {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || QueryError(format!("Invalid year or year range: {}", s));
+        let year = |year: &str| {
+            let year = year.trim();
+            match year.len() == 4 && year.chars().all(|c| c.is_ascii_digit()) {
+                true => year.parse::<u16>().map_err(|_| error()),
+                false => Err(error()),
+            }
+        };
+        match s.split_once('-') {
+            None => Ok(Self {
+                start: year(s)?,
+                end: None,
+            }),
+            Some((start, end)) => {
+                let (start, end) = (year(start)?, year(end)?);
+                match start <= end {
+                    true => Ok(Self {
+                        start,
+                        end: Some(end),
+                    }),
+                    false => Err(error()),
+                }
+            }
+        }
+    }
+}
+
+impl Display for YearRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.end {
+            None => write!(f, "{}", self.start),
+            Some(end) => write!(f, "{}-{}", self.start, end),
+        }
+    }
+}
+
+/// International Standard Recording Code: country, registrant, year and designation (`USVI20400001`)
+#[derive(Debug, Clone)]
+pub struct Isrc(String);
+
+impl FromStr for Isrc {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let isrc = s.replace('-', "").to_ascii_uppercase();
+        let bytes = isrc.as_bytes();
+        let valid = bytes.len() == 12
+            && bytes[..2].iter().all(u8::is_ascii_alphabetic)
+            && bytes[2..5].iter().all(u8::is_ascii_alphanumeric)
+            && bytes[5..].iter().all(u8::is_ascii_digit);
+        match valid {
+            true => Ok(Self(isrc)),
+            false => Err(QueryError(format!("Invalid ISRC: {}", s))),
+        }
+    }
+}
+
+/// Universal Product Code, or its 13 digits EAN form
+#[derive(Debug, Clone)]
+pub struct Upc(String);
+
+impl FromStr for Upc {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upc = s.trim();
+        match matches!(upc.len(), 12 | 13) && upc.chars().all(|c| c.is_ascii_digit()) {
+            true => Ok(Self(upc.to_string())),
+            false => Err(QueryError(format!("Invalid UPC: {}", s))),
+        }
+    }
+}
+
+/// Builder of the `q` parameter of the spotify search with the field filters
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    keywords: Option<String>,
+    filters: Vec<(&'static str, String)>,
+}
+
+impl SearchQuery {
+    pub fn new(keywords: Option<String>) -> Self {
+        Self {
+            keywords,
+            filters: vec![],
+        }
+    }
+
+    fn filter(mut self, field: &'static str, value: Option<String>) -> Self {
+        if let Some(value) = value {
+            self.filters.push((field, value))
+        }
+        self
+    }
+
+    pub fn artist(self, artist: Option<String>) -> Self {
+        self.filter("artist", artist)
+    }
+
+    pub fn album(self, album: Option<String>) -> Self {
+        self.filter("album", album)
+    }
+
+    pub fn year(self, year: Option<YearRange>) -> Self {
+        self.filter("year", year.map(|year| year.to_string()))
+    }
+
+    pub fn genre(self, genre: Option<String>) -> Self {
+        self.filter("genre", genre)
+    }
+
+    pub fn isrc(self, isrc: Option<Isrc>) -> Self {
+        self.filter("isrc", isrc.map(|isrc| isrc.0))
+    }
+
+    pub fn upc(self, upc: Option<Upc>) -> Self {
+        self.filter("upc", upc.map(|upc| upc.0))
+    }
+
+    pub fn tags(self, tags: &[SearchTag]) -> Self {
+        tags.iter().fold(self, |query, tag| {
+            query.filter("tag", Some(tag.to_string()))
+        })
+    }
+
+    /// Quote the value if it contains spaces, the double quotes can't be escaped in a spotify query
+    fn quote(field: &str, value: &str) -> Result<String, QueryError> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(QueryError(format!("Empty value for the {} filter", field)));
+        }
+        if value.contains('"') {
+            return Err(QueryError(format!(
+                "The {} filter can't contain a double quote: {}",
+                field, value
+            )));
+        }
+        match value.contains(char::is_whitespace) {
+            true => Ok(format!("{}:\"{}\"", field, value)),
+            false => Ok(format!("{}:{}", field, value)),
+        }
+    }
+
+    pub fn build(&self) -> Result<String, QueryError> {
+        let mut parts = self
+            .keywords
+            .iter()
+            .map(|keywords| keywords.trim().to_string())
+            .filter(|keywords| !keywords.is_empty())
+            .collect::<Vec<_>>();
+        for (field, value) in self.filters.iter() {
+            parts.push(Self::quote(field, value)?)
+        }
+        match parts.is_empty() {
+            true => Err(QueryError("Empty search query".into())),
+            false => Ok(parts.join(" ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Isrc, SearchQuery, SearchTag, Upc, YearRange};
+
+    #[test]
+    fn year_range() {
+        assert_eq!("1997".parse::<YearRange>().unwrap().to_string(), "1997");
+        assert_eq!(
+            "1990 - 1999".parse::<YearRange>().unwrap().to_string(),
+            "1990-1999"
+        );
+        assert_eq!(
+            "2001-2001".parse::<YearRange>().unwrap().to_string(),
+            "2001-2001"
+        );
+        for invalid in [
+            "",
+            "97",
+            "19977",
+            "199a",
+            "1999-1990",
+            "1990-",
+            "-1990",
+            "+997",
+        ] {
+            assert!(invalid.parse::<YearRange>().is_err(), "{}", invalid)
+        }
+    }
+
+    #[test]
+    fn isrc() {
+        assert_eq!("USVI20400001".parse::<Isrc>().unwrap().0, "USVI20400001");
+        assert_eq!("us-vi2-04-00001".parse::<Isrc>().unwrap().0, "USVI20400001");
+        for invalid in [
+            "",
+            "USVI2040000",
+            "USVI204000011",
+            "1SVI20400001",
+            "USVI2040000A",
+        ] {
+            assert!(invalid.parse::<Isrc>().is_err(), "{}", invalid)
+        }
+    }
+
+    #[test]
+    fn upc() {
+        assert_eq!("724384960650".parse::<Upc>().unwrap().0, "724384960650");
+        assert_eq!(" 0724384960650 ".parse::<Upc>().unwrap().0, "0724384960650");
+        for invalid in ["", "72438496065", "07243849606501", "72438496065a"] {
+            assert!(invalid.parse::<Upc>().is_err(), "{}", invalid)
+        }
+    }
+
+    #[test]
+    fn quote() {
+        assert_eq!(
+            SearchQuery::quote("artist", " Daft Punk ").unwrap(),
+            "artist:\"Daft Punk\""
+        );
+        assert_eq!(SearchQuery::quote("genre", "house").unwrap(), "genre:house");
+        assert!(SearchQuery::quote("album", "  ").is_err());
+        assert!(SearchQuery::quote("album", "\"Discovery\"").is_err());
+    }
+
+    #[test]
+    fn build() {
+        let query = SearchQuery::new(Some(" one more time ".into()))
+            .artist(Some("Daft Punk".into()))
+            .year(Some("2000-2001".parse().unwrap()))
+            .isrc(Some("GBDUW0000053".parse().unwrap()))
+            .tags(&[SearchTag::Hipster]);
+        assert_eq!(
+            query.build().unwrap(),
+            "one more time artist:\"Daft Punk\" year:2000-2001 isrc:GBDUW0000053 tag:hipster"
+        );
+        let query = SearchQuery::new(None).upc(Some("724384960650".parse().unwrap()));
+        assert_eq!(query.build().unwrap(), "upc:724384960650");
+        assert!(SearchQuery::new(Some(" ".into())).build().is_err());
+        assert!(SearchQuery::new(None)
+            .album(Some("a \"b\"".into()))
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,273 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::Serialize;
+
+use super::{
+    error::SpotifyResult,
+    spotify::{Artist, Spotify},
+};
+
+#[derive(Debug, Serialize)]
+pub struct ArtistNode {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) genres: Vec<String>,
+    pub(crate) popularity: i32,
+    /// Number of hops from the crawled artist
+    pub(crate) depth: u8,
+}
+
+impl ArtistNode {
+    fn new(artist: Artist, depth: u8) -> Self {
+        Self {
+            id: artist.id,
+            name: artist.name,
+            genres: artist.genres,
+            popularity: artist.popularity,
+            depth,
+        }
+    }
+}
+
+/// `source` lists `target` among its related artists
+#[derive(Debug, Serialize)]
+pub struct RelatedEdge {
+    pub(crate) source: String,
+    pub(crate) target: String,
+}
+
+/// Graph of the related artists reached from an artist
+#[derive(Debug, Serialize)]
+pub struct RelatedGraph {
+    pub(crate) nodes: Vec<ArtistNode>,
+    pub(crate) edges: Vec<RelatedEdge>,
+}
+
+impl RelatedGraph {
+    /// Walk the related artists breadth first, up to `max_depth` hops and `max_nodes` artists.
+    ///
+    /// The requests go through the rate limited executor, one at a time.
+    /// An artist whose related artists can't be fetched is kept as a leaf.
+    pub async fn crawl(
+        spotify: &Spotify,
+        artist_id: &str,
+        max_depth: u8,
+        max_nodes: usize,
+    ) -> SpotifyResult<Self> {
+        let seed = spotify.artist(artist_id).await?;
+        let mut graph = Self {
+            nodes: vec![ArtistNode::new(seed, 0)],
+            edges: vec![],
+        };
+        let mut visited = HashSet::from([graph.nodes[0].id.clone()]);
+        let mut queue = VecDeque::from([0]);
+        while let Some(index) = queue.pop_front() {
+            let (id, depth) = (graph.nodes[index].id.clone(), graph.nodes[index].depth);
+            if depth >= max_depth {
+                continue;
+            }
+            let related = match spotify.related_artists(&id).await {
+                Ok(related) => related,
+                Err(e) => {
+                    eprintln!("Unable to crawl {} : {}", graph.nodes[index].name, e);
+                    continue;
+                }
+            };
+            for artist in related {
+                let target = artist.id.clone();
+                if !visited.contains(&target) {
+                    if graph.nodes.len() >= max_nodes {
+                        continue;
+                    }
+                    let _ = visited.insert(target.clone());
+                    queue.push_back(graph.nodes.len());
+                    graph.nodes.push(ArtistNode::new(artist, depth + 1));
+                }
+                graph.edges.push(RelatedEdge {
+                    source: id.clone(),
+                    target,
+                });
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Number of crawled artists listing each artist as related
+    fn in_degrees(&self) -> HashMap<&str, usize> {
+        self.edges.iter().fold(HashMap::new(), |mut degrees, edge| {
+            *degrees.entry(edge.target.as_str()).or_insert(0) += 1;
+            degrees
+        })
+    }
+
+    /// Artists, except the crawled one, related to at least `min_links` crawled artists.
+    /// The most linked come first
+    pub fn overlapping(&self, min_links: usize) -> Vec<(&ArtistNode, usize)> {
+        let degrees = self.in_degrees();
+        let mut nodes = self
+            .nodes
+            .iter()
+            .skip(1)
+            .map(|node| (node, degrees.get(node.id.as_str()).copied().unwrap_or(0)))
+            .filter(|(_, links)| *links >= min_links)
+            .collect::<Vec<_>>();
+        nodes.sort_by(|(_, lhs), (_, rhs)| rhs.cmp(lhs));
+        nodes
+    }
+
+    pub fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph related_artists {\n");
+        for node in self.nodes.iter() {
+            dot.push_str(
+                format!(
+                    "    \"{}\" [label=\"{}\", genres=\"{}\", popularity={}, depth={}];\n",
+                    node.id,
+                    escape(&node.name),
+                    escape(&node.genres.join(";")),
+                    node.popularity,
+                    node.depth
+                )
+                .as_str(),
+            )
+        }
+        for edge in self.edges.iter() {
+            dot.push_str(format!("    \"{}\" -> \"{}\";\n", edge.source, edge.target).as_str())
+        }
+        dot.push('}');
+        dot
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let escape = |s: &str| {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        };
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        for (key, key_type) in [
+            ("name", "string"),
+            ("genres", "string"),
+            ("popularity", "int"),
+            ("depth", "int"),
+        ] {
+            xml.push_str(
+                format!(
+                    "  <key id=\"{0}\" for=\"node\" attr.name=\"{0}\" attr.type=\"{1}\"/>\n",
+                    key, key_type
+                )
+                .as_str(),
+            )
+        }
+        xml.push_str("  <graph id=\"related_artists\" edgedefault=\"directed\">\n");
+        for node in self.nodes.iter() {
+            xml.push_str(format!("    <node id=\"{}\">\n", node.id).as_str());
+            xml.push_str(
+                format!("      <data key=\"name\">{}</data>\n", escape(&node.name)).as_str(),
+            );
+            xml.push_str(
+                format!(
+                    "      <data key=\"genres\">{}</data>\n",
+                    escape(&node.genres.join(";"))
+                )
+                .as_str(),
+            );
+            xml.push_str(
+                format!(
+                    "      <data key=\"popularity\">{}</data>\n",
+                    node.popularity
+                )
+                .as_str(),
+            );
+            xml.push_str(format!("      <data key=\"depth\">{}</data>\n", node.depth).as_str());
+            xml.push_str("    </node>\n");
+        }
+        for edge in self.edges.iter() {
+            xml.push_str(
+                format!(
+                    "    <edge source=\"{}\" target=\"{}\"/>\n",
+                    edge.source, edge.target
+                )
+                .as_str(),
+            )
+        }
+        xml.push_str("  </graph>\n</graphml>");
+        xml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArtistNode, RelatedEdge, RelatedGraph};
+
+    fn graph() -> RelatedGraph {
+        let node = |id: &str, name: &str, genres: &[&str], depth| ArtistNode {
+            id: id.to_string(),
+            name: name.to_string(),
+            genres: genres.iter().map(|genre| genre.to_string()).collect(),
+            popularity: 50,
+            depth,
+        };
+        RelatedGraph {
+            nodes: vec![
+                node("a", "Simon & Garfunkel", &["folk"], 0),
+                node("b", "The \"Band\" <live>", &["r&b", "soul"], 1),
+                node("c", "Back\\slash", &[], 1),
+            ],
+            edges: vec![
+                RelatedEdge {
+                    source: "a".to_string(),
+                    target: "b".to_string(),
+                },
+                RelatedEdge {
+                    source: "a".to_string(),
+                    target: "c".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn dot_escaping() {
+        let dot = graph().to_dot();
+        assert!(dot.contains(
+            r#"    "a" [label="Simon & Garfunkel", genres="folk", popularity=50, depth=0];"#
+        ));
+        assert!(dot.contains(
+            r#"    "b" [label="The \"Band\" <live>", genres="r&b;soul", popularity=50, depth=1];"#
+        ));
+        assert!(dot.contains(r#"label="Back\\slash""#));
+        assert!(dot.contains(r#"    "a" -> "b";"#));
+        assert!(dot.starts_with("digraph related_artists {\n"));
+        assert!(dot.ends_with('}'));
+    }
+
+    #[test]
+    fn graphml_escaping() {
+        let xml = graph().to_graphml();
+        assert!(xml.contains(r#"<data key="name">Simon &amp; Garfunkel</data>"#));
+        assert!(xml.contains(r#"<data key="name">The &quot;Band&quot; &lt;live&gt;</data>"#));
+        assert!(xml.contains(r#"<data key="genres">r&amp;b;soul</data>"#));
+        assert!(xml.contains(r#"<edge source="a" target="c"/>"#));
+        assert!(xml.ends_with("</graphml>"));
+    }
+}
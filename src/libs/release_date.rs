@@ -0,0 +1,215 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Release date with the precision given by spotify (`release_date_precision`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ReleaseDate {
+    Year(i32),
+    Month(i32, u32),
+    Day(NaiveDate),
+}
+
+#[derive(Debug)]
+pub struct ReleaseDateError(String);
+
+impl Display for ReleaseDateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid release date: {}", self.0)
+    }
+}
+
+impl ReleaseDate {
+    pub fn year(&self) -> i32 {
+        match self {
+            ReleaseDate::Year(year) | ReleaseDate::Month(year, _) => *year,
+            ReleaseDate::Day(date) => date.year(),
+        }
+    }
+
+    /// Missing components are the lowest, so a less precise date comes first
+    fn components(&self) -> (i32, u32, u32) {
+        match self {
+            ReleaseDate::Year(year) => (*year, 0, 0),
+            ReleaseDate::Month(year, month) => (*year, *month, 0),
+            ReleaseDate::Day(date) => (date.year(), date.month(), date.day()),
+        }
+    }
+}
+
+impl PartialOrd for ReleaseDate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ReleaseDate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.components().cmp(&other.components())
+    }
+}
+
+impl FromStr for ReleaseDate {
+    type Err = ReleaseDateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ReleaseDateError(s.to_string());
+        // A component of exactly `len` digits
+        let component = |part: Option<&str>, len: usize| {
+            part.map(
+                |part| match part.len() == len && part.bytes().all(|b| b.is_ascii_digit()) {
+                    true => part.parse::<u32>().map_err(|_| error()),
+                    false => Err(error()),
+                },
+            )
+            .transpose()
+        };
+        let mut parts = s.trim().split('-');
+        let year = component(parts.next(), 4)?.ok_or_else(error)? as i32;
+        let month = match component(parts.next(), 2)? {
+            None => return Ok(ReleaseDate::Year(year)),
+            Some(month) if (1..=12).contains(&month) => month,
+            Some(_) => return Err(error()),
+        };
+        let day = match component(parts.next(), 2)? {
+            None => return Ok(ReleaseDate::Month(year, month)),
+            Some(day) => day,
+        };
+        match parts.next() {
+            Some(_) => Err(error()),
+            None => NaiveDate::from_ymd_opt(year, month, day)
+                .map(ReleaseDate::Day)
+                .ok_or_else(error),
+        }
+    }
+}
+
+impl TryFrom<String> for ReleaseDate {
+    type Error = ReleaseDateError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ReleaseDate> for String {
+    fn from(value: ReleaseDate) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for ReleaseDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReleaseDate::Year(year) => write!(f, "{:04}", year),
+            ReleaseDate::Month(year, month) => write!(f, "{:04}-{:02}", year, month),
+            ReleaseDate::Day(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::ReleaseDate;
+
+    fn date(s: &str) -> ReleaseDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn precisions() {
+        assert_eq!(date("1979"), ReleaseDate::Year(1979));
+        assert_eq!(date("1979-10"), ReleaseDate::Month(1979, 10));
+        assert_eq!(
+            date("1979-10-05"),
+            ReleaseDate::Day(NaiveDate::from_ymd_opt(1979, 10, 5).unwrap())
+        );
+        assert_eq!(date(" 1979-10 "), ReleaseDate::Month(1979, 10));
+    }
+
+    #[test]
+    fn invalid() {
+        for s in [
+            "",
+            "79",
+            "197",
+            "19790",
+            "year",
+            "1979-13",
+            "1979-00",
+            "1979-1",
+            "1979-10-5",
+            "1979-02-30",
+            "1979-10-05-01",
+            "1979-10-",
+            "+979",
+        ] {
+            assert!(s.parse::<ReleaseDate>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn display() {
+        for s in ["1979", "1979-10", "1979-10-05", "0999"] {
+            assert_eq!(date(s).to_string(), s);
+        }
+        assert_eq!(date("1979-10-05").year(), 1979);
+    }
+
+    #[test]
+    fn order() {
+        assert!(date("1979") < date("1979-01"));
+        assert!(date("1979-01") < date("1979-01-01"));
+        assert!(date("1979-10-05") < date("1979-11"));
+        assert!(date("1979-12-31") < date("1980"));
+        assert!(date("1978") < date("1979-01-01"));
+        let mut dates = vec![
+            date("1980"),
+            date("1979-10-05"),
+            date("1979"),
+            date("1979-10"),
+        ];
+        dates.sort();
+        assert_eq!(
+            dates,
+            vec![
+                date("1979"),
+                date("1979-10"),
+                date("1979-10-05"),
+                date("1980")
+            ]
+        );
+    }
+
+    #[test]
+    fn serde() {
+        let json = serde_json::to_string(&date("1979-10")).unwrap();
+        assert_eq!(json, "\"1979-10\"");
+        assert_eq!(
+            serde_json::from_str::<ReleaseDate>(&json).unwrap(),
+            date("1979-10")
+        );
+        assert!(serde_json::from_str::<ReleaseDate>("\"79\"").is_err());
+    }
+}
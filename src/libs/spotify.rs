@@ -17,92 +17,285 @@
 
 #![allow(unused)]
 
-use chrono::NaiveDate;
+use chrono::Utc;
+use clap::ArgEnum;
 use image::DynamicImage;
-use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display, process::exit};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Display, fs::OpenOptions, process::exit};
+use tokio::sync::RwLock;
 use viuer::resize;
 
 use base64::encode;
-use reqwest::{RequestBuilder, StatusCode};
+use reqwest::{RequestBuilder, Response, StatusCode};
 use serde_json::Value;
 
-use crate::libs::util;
+use crate::{
+    config::{accounts_base_url, api_base_url, koto_base_dir, KOTO_TOKEN_CACHE},
+    libs::{
+        cache::ResponseCache,
+        error::{from_json, SpotifyError, SpotifyResult},
+        executor::RequestExecutor,
+        paging::{Page, Paginator},
+        release_date::ReleaseDate,
+        util,
+    },
+};
+
+/// Seconds before the real expiration from which a token is considered expired
+const TOKEN_EXPIRATION_MARGIN: i64 = 60;
 
-#[derive(Debug, Clone)]
+/// Maximum number of ids in an audio features request
+const AUDIO_FEATURES_BATCH_SIZE: usize = 100;
+/// Maximum number of ids of a several artists request
+const ARTISTS_BATCH_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Token {
     access_token: String,
     token_type: String,
     expire_in: u32,
+    /// Unix timestamp of the token expiration
+    #[serde(default)]
+    expire_at: i64,
+}
+
+/// Body of the accounts service response
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String,
+    expires_in: u32,
 }
 
 impl Token {
-    pub async fn new(client_id: &str, client_secret: &str) -> Option<Self> {
+    pub async fn new(
+        executor: &RequestExecutor,
+        client_id: &str,
+        client_secret: &str,
+    ) -> SpotifyResult<Self> {
         let creditential = format!("{}:{}", client_id, client_secret);
         let encoded = format!("Basic {}", encode(creditential));
-        let request = reqwest::Client::new()
-            .post("https://accounts.spotify.com/api/token")
+        let rb = reqwest::Client::new()
+            .post(format!("{}/api/token", accounts_base_url()))
             .header("Authorization", encoded)
             .header("Content-Type", "application/x-www-form-urlencoded")
-            .body("grant_type=client_credentials")
-            .send()
-            .await;
-        if let Ok(r) = request {
-            if r.status() != StatusCode::OK {
-                None
-            } else {
-                let value = r.json::<Value>().await.ok()?;
-                let access_token = value.as_object()?["access_token"].as_str()?.to_string();
-                let token_type = value.as_object()?["token_type"].as_str()?.to_string();
-                let expire_in: u32 = value.as_object()?["expires_in"].as_u64()? as u32;
-                Some(Self {
-                    access_token,
-                    token_type,
-                    expire_in,
-                })
-            }
-        } else {
-            None
+            .body("grant_type=client_credentials");
+        let response = executor.execute(rb).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(SpotifyError::from_response(status, &body));
+        }
+        let TokenResponse {
+            access_token,
+            token_type,
+            expires_in,
+        } = from_json(&body)?;
+        Ok(Self {
+            access_token,
+            token_type,
+            expire_in: expires_in,
+            expire_at: Utc::now().timestamp() + expires_in as i64,
+        })
+    }
+
+    /// Request a new token with the credentials found in the environment
+    /// and save it in the cache
+    pub async fn from_env(executor: &RequestExecutor) -> SpotifyResult<Self> {
+        let client_id = std::env::var("CLIENT_ID").map_err(|_| SpotifyError::MissingCredentials)?;
+        let client_secret =
+            std::env::var("CLIENT_SECRET").map_err(|_| SpotifyError::MissingCredentials)?;
+        let token = Self::new(executor, client_id.as_str(), client_secret.as_str()).await?;
+        let () = token.save();
+        Ok(token)
+    }
+
+    /// Token used when replaying a cassette, the api is never reached
+    pub fn replay() -> Self {
+        Self {
+            access_token: String::new(),
+            token_type: "Bearer".into(),
+            expire_in: 0,
+            expire_at: i64::MAX,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Utc::now().timestamp() + TOKEN_EXPIRATION_MARGIN >= self.expire_at
+    }
+
+    /// Retrieve the cached token if it's still valid
+    pub fn from_cache() -> Option<Self> {
+        let path = koto_base_dir().find_cache_file(KOTO_TOKEN_CACHE)?;
+        let file = OpenOptions::new().read(true).open(path).ok()?;
+        let token = serde_json::from_reader::<_, Self>(file).ok()?;
+        match token.is_expired() {
+            true => None,
+            false => Some(token),
+        }
+    }
+
+    pub fn save(&self) {
+        let path = match koto_base_dir().place_cache_file(KOTO_TOKEN_CACHE) {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path);
+        if let Ok(file) = file {
+            let _ = serde_json::to_writer(file, self);
         }
     }
 }
 
 pub struct Spotify {
-    token: Token,
+    /// Requested on the first request needing it, so cached responses can be served without it
+    token: RwLock<Option<Token>>,
+    executor: RequestExecutor,
+    cache: ResponseCache,
 }
 
 impl Spotify {
-    pub fn new(token: &Token) -> Self {
+    pub fn new(token: Option<Token>, executor: RequestExecutor, cache: ResponseCache) -> Self {
         Self {
-            token: token.clone(),
+            token: RwLock::new(token),
+            executor,
+            cache,
         }
     }
-    pub async fn init() -> Self {
-        let client_id = std::env::var("CLIENT_ID").unwrap();
-        let client_secret = std::env::var("CLIENT_SECRET").unwrap();
-        let token = Token::new(client_id.as_str(), client_secret.as_str())
+    /// Client with the environment credentials, the cached responses aren't used with `no_cache`
+    pub async fn init(no_cache: bool) -> SpotifyResult<Self> {
+        let executor = RequestExecutor::from_env()?;
+        let token = match executor.is_replaying() {
+            true => Some(Token::replay()),
+            false => Token::from_cache(),
+        };
+        Ok(Self::new(token, executor, ResponseCache::new(!no_cache)))
+    }
+
+    /// Replace the current token by a new one
+    async fn refresh_token(&self) -> SpotifyResult<()> {
+        let mut token = self.token.write().await;
+        *token = Some(Token::from_env(&self.executor).await?);
+        Ok(())
+    }
+
+    /// The current token, a new one is requested if there is none yet or if it expired
+    async fn access_token(&self) -> SpotifyResult<String> {
+        let valid = self
+            .token
+            .read()
             .await
-            .unwrap_or_else(|| panic!("Unable to connect to the api"));
-        Self::new(&token)
+            .as_ref()
+            .filter(|token| !token.is_expired())
+            .map(|token| token.access_token.clone());
+        match valid {
+            Some(access_token) => Ok(access_token),
+            None => {
+                let () = self.refresh_token().await?;
+                let token = self.token.read().await;
+                Ok(token
+                    .as_ref()
+                    .map(|token| token.access_token.clone())
+                    .unwrap_or_default())
+            }
+        }
+    }
+
+    /// Send an authorized request through the executor.
+    /// The token is refreshed and the request sent again if the api rejects the token
+    async fn send(&self, rb: RequestBuilder) -> SpotifyResult<Response> {
+        let retry = rb.try_clone();
+        let access_token = self.access_token().await?;
+        let response = self.executor.execute(rb.bearer_auth(access_token)).await?;
+        let rb = match (response.status(), retry) {
+            (StatusCode::UNAUTHORIZED, Some(rb)) => rb,
+            _ => return Ok(response),
+        };
+        let () = self.refresh_token().await?;
+        let access_token = self.access_token().await?;
+        let response = self.executor.execute(rb.bearer_auth(access_token)).await?;
+        Ok(response)
+    }
+
+    /// Send the request and return the body of the successful response
+    async fn get_body(&self, rb: RequestBuilder) -> SpotifyResult<String> {
+        let response = self.send(rb).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        match status.is_success() {
+            true => Ok(body),
+            false => Err(SpotifyError::from_response(status, &body)),
+        }
     }
+
+    /// Send the request and deserialize the response
+    pub(crate) async fn get<T: DeserializeOwned>(&self, rb: RequestBuilder) -> SpotifyResult<T> {
+        let body = self.get_body(rb).await?;
+        from_json(&body)
+    }
+
+    /// Same as [Spotify::get] but the response is looked up in the cache first.
+    /// An expired response is still used if the api can't be reached
+    pub(crate) async fn get_cached<T: DeserializeOwned>(
+        &self,
+        r_type: &SpotifyRessourceType,
+        id: &str,
+        rb: RequestBuilder,
+    ) -> SpotifyResult<T> {
+        let query = rb
+            .try_clone()
+            .and_then(|rb| rb.build().ok())
+            .and_then(|request| request.url().query().map(String::from));
+        let key = ResponseCache::key(id, query.as_deref());
+        if let Some(body) = self.cache.get(r_type, &key) {
+            return from_json(&body);
+        }
+        match self.get_body(rb).await {
+            Ok(body) => {
+                let () = self.cache.put(r_type, &key, &body);
+                from_json(&body)
+            }
+            Err(SpotifyError::Network(e)) => match self.cache.get_stale(r_type, &key) {
+                Some(body) => from_json(&body),
+                None => Err(SpotifyError::Network(e)),
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Request of a page given by the `next` url of the previous one
+    pub(crate) fn page_request(&self, url: String) -> RequestBuilder {
+        reqwest::Client::new()
+            .get(url)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+    }
+
     fn search_end_point() -> String {
-        "https://api.spotify.com/v1/search".into()
+        format!("{}/search", api_base_url())
+    }
+    fn new_releases_end_point() -> String {
+        format!("{}/browse/new-releases", api_base_url())
     }
     fn track_end_point() -> String {
-        "https://api.spotify.com/v1/tracks/".into()
+        format!("{}/tracks/", api_base_url())
     }
     fn album_end_point() -> String {
-        "https://api.spotify.com/v1/albums/".into()
+        format!("{}/albums/", api_base_url())
     }
 
     fn artist_end_point() -> String {
-        "https://api.spotify.com/v1/artists/".into()
+        format!("{}/artists/", api_base_url())
     }
     fn audio_feature_end_point() -> String {
-        "https://api.spotify.com/v1/audio-features/".into()
+        format!("{}/audio-features/", api_base_url())
     }
     fn audio_analysis_end_point() -> String {
-        "https://api.spotify.com/v1/audio-analysis/".into()
+        format!("{}/audio-analysis/", api_base_url())
     }
 
     fn create_url(
@@ -114,11 +307,11 @@ impl Spotify {
         offset: Option<u32>,
         included_genre: Vec<SpotifyIncludeGroupe>,
     ) -> RequestBuilder {
-        let ids = if spotify_ids.len() > 1 {
+        let (end_point, ids) = if spotify_ids.len() > 1 {
             let s = String::from("?ids=") + &spotify_ids.join(",").to_string();
-            s
+            (end_point.trim_end_matches('/'), s)
         } else {
-            spotify_ids.first().unwrap().clone()
+            (end_point.as_str(), spotify_ids.first().unwrap().clone())
         };
         let base_url = format!("{}{}", end_point, ids);
         let base_url = match r_type {
@@ -182,10 +375,6 @@ impl Spotify {
             included_genre,
         );
         let rb = rb
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.token.access_token),
-            )
             .header("Accept", "application/json")
             .header("Content-Type", "application/json");
         rb
@@ -220,10 +409,6 @@ impl Spotify {
             builder = builder.query(&[("include_external", "audio")])
         }
         builder = builder
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.token.access_token),
-            )
             .header("Accept", "application/json")
             .header("Content-Type", "application/json");
         builder
@@ -236,27 +421,60 @@ impl Spotify {
         limit: Option<u8>,
         offset: Option<u32>,
         include_external: Option<bool>,
-    ) -> Option<HashMap<SpotifySearchKey, SpotifySearchResult>> {
-        // let response = self.setup_search_request(query, item_type, market, limit, offset, include_external).send().await;
-        // match response {
-        //     Err(e) => {println!("Errror search : {:?}", e); None},
-        //     Ok(re) => {
-        //         println!("{:?}", &re.json::<Value>().await);
-        //         unreachable!("End");
-        //         re.json::<HashMap<SpotifySearchKey, SpotifySearchResult>>()
-        //         .await
-        //         .ok()
-        //     },
-        // }
-        self.setup_search_request(query, item_type, market, limit, offset, include_external)
-            .send()
-            .await
-            .ok()?
-            .json::<HashMap<SpotifySearchKey, SpotifySearchResult>>()
+    ) -> SpotifyResult<HashMap<SpotifySearchKey, SpotifySearchResult>> {
+        let rb =
+            self.setup_search_request(query, item_type, market, limit, offset, include_external);
+        self.get(rb).await
+    }
+
+    /// Search restricted to tracks, with their full details
+    pub async fn search_tracks(
+        &self,
+        query: &str,
+        market: Option<String>,
+        limit: Option<u8>,
+    ) -> SpotifyResult<Vec<Track>> {
+        let rb = self.setup_search_request(
+            query,
+            vec![SpotifySearchType::Track],
+            market,
+            limit,
+            None,
+            None,
+        );
+        self.get::<TrackSearch>(rb)
             .await
-            .ok()
+            .map(|search| search.tracks.items)
     }
-    pub async fn artist(&self, artist_id: &str) -> Option<Artist> {
+
+    /// First results of a search for a single ressource type
+    pub async fn search_candidates(
+        &self,
+        query: &str,
+        item_type: SpotifySearchType,
+        limit: u8,
+    ) -> SpotifyResult<Vec<SpotifySearchResultItem>> {
+        let result = self
+            .search(query, vec![item_type], None, Some(limit), Some(0), None)
+            .await?;
+        Ok(result
+            .into_values()
+            .next()
+            .map(|result| result.items)
+            .unwrap_or_default())
+    }
+
+    /// Iterate over all the results of a search for a single ressource type
+    pub fn search_pages(
+        &self,
+        query: &str,
+        item_type: SpotifySearchType,
+        market: Option<String>,
+    ) -> Paginator<'_, SearchPage> {
+        let rb = self.setup_search_request(query, vec![item_type], market, Some(50), None, None);
+        Paginator::new(self, rb)
+    }
+    pub async fn artist(&self, artist_id: &str) -> SpotifyResult<Artist> {
         let rb = self.setup_url_request(
             &SpotifyRessourceType::Artist,
             vec![artist_id.into()],
@@ -265,26 +483,85 @@ impl Spotify {
             None,
             vec![],
         );
-        if let Ok(response) = rb.send().await {
-            response.json::<Artist>().await.ok()
-        } else {
-            None
+        self.get_cached(&SpotifyRessourceType::Artist, artist_id, rb)
+            .await
+    }
+
+    /// Fetch several artists, the cached ones aren't requested again
+    pub async fn artists(&self, artist_ids: &[String]) -> SpotifyResult<Vec<Artist>> {
+        let r_type = SpotifyRessourceType::Artist;
+        let mut artists = vec![];
+        let mut missing = vec![];
+        for id in artist_ids {
+            match self.cache.get(&r_type, &ResponseCache::key(id, None)) {
+                Some(body) => artists.push(from_json::<Artist>(&body)?),
+                None => missing.push(id.clone()),
+            }
+        }
+        for chunk in missing.chunks(ARTISTS_BATCH_SIZE) {
+            // A single id is requested on the artist endpoint
+            if let [id] = chunk {
+                artists.push(self.artist(id).await?);
+                continue;
+            }
+            let rb = self.setup_url_request(&r_type, chunk.to_vec(), None, None, None, vec![]);
+            let response = self.get::<ArtistsResponse>(rb).await?;
+            for value in response.artists.into_iter().flatten() {
+                let body = value.to_string();
+                let artist = from_json::<Artist>(&body)?;
+                let () = self
+                    .cache
+                    .put(&r_type, &ResponseCache::key(&artist.id, None), &body);
+                artists.push(artist);
+            }
         }
+        Ok(artists)
     }
 
-    pub async fn album(&self, album_id: String) -> Option<Album> {
+    /// Iterate over the albums featured in the new releases
+    pub fn new_releases(
+        &self,
+        market: Option<String>,
+        offset: Option<u32>,
+    ) -> Paginator<'_, NewReleases> {
+        let mut rb = reqwest::Client::new()
+            .get(Self::new_releases_end_point())
+            .query(&[("limit", 50)]);
+        if let Some(market) = market {
+            rb = rb.query(&[("country", market)]);
+        }
+        if let Some(offset) = offset {
+            rb = rb.query(&[("offset", offset)]);
+        }
+        let rb = rb
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json");
+        Paginator::new(self, rb)
+    }
+
+    /// Fetch the album with all its tracks
+    pub async fn album(&self, album_id: String) -> SpotifyResult<Album> {
         let rb = self.setup_url_request(
             &SpotifyRessourceType::Album,
-            vec![album_id],
+            vec![album_id.clone()],
             None,
             None,
             None,
             vec![],
         );
-        rb.send().await.ok()?.json().await.ok()
+        let mut album = self
+            .get_cached::<Album>(&SpotifyRessourceType::Album, &album_id, rb)
+            .await?;
+        if let Some(next) = album.tracks.next.take() {
+            let tracks = Paginator::<SpotifyAlbumTrackResult>::new(self, self.page_request(next))
+                .collect()
+                .await?;
+            album.tracks.items.extend(tracks);
+        }
+        Ok(album)
     }
 
-    pub async fn _artists(&self, artist_ids: Vec<String>) -> Option<Vec<Value>> {
+    pub async fn _artists(&self, artist_ids: Vec<String>) -> SpotifyResult<Vec<Value>> {
         let rb = self.setup_url_request(
             &SpotifyRessourceType::Artist,
             artist_ids,
@@ -293,22 +570,138 @@ impl Spotify {
             None,
             vec![],
         );
-        if let Ok(response) = rb.send().await {
-            response.json::<Vec<Value>>().await.ok()
-        } else {
-            None
+        self.get(rb).await
+    }
+    pub async fn track(&self, track_id: &str) -> SpotifyResult<Track> {
+        let rb = self.setup_url_request(
+            &SpotifyRessourceType::Track,
+            vec![track_id.into()],
+            None,
+            None,
+            None,
+            vec![],
+        );
+        self.get_cached(&SpotifyRessourceType::Track, track_id, rb)
+            .await
+    }
+
+    pub async fn artist_top_tracks(
+        &self,
+        artist_id: &str,
+        market: Option<String>,
+    ) -> SpotifyResult<Vec<Track>> {
+        let rb = self.setup_url_request(
+            &SpotifyRessourceType::ArtistTopTrack,
+            vec![artist_id.into()],
+            market,
+            None,
+            None,
+            vec![],
+        );
+        self.get_cached::<TopTracks>(&SpotifyRessourceType::ArtistTopTrack, artist_id, rb)
+            .await
+            .map(|top_tracks| top_tracks.tracks)
+    }
+
+    pub async fn audio_features(&self, track_id: &str) -> SpotifyResult<AudioFeatures> {
+        let rb = self.setup_url_request(
+            &SpotifyRessourceType::AudioFeature,
+            vec![track_id.into()],
+            None,
+            None,
+            None,
+            vec![],
+        );
+        self.get_cached(&SpotifyRessourceType::AudioFeature, track_id, rb)
+            .await
+    }
+
+    pub async fn audio_analysis(&self, track_id: &str) -> SpotifyResult<AudioAnalysis> {
+        let rb = self.setup_url_request(
+            &SpotifyRessourceType::AudioAnalysis,
+            vec![track_id.into()],
+            None,
+            None,
+            None,
+            vec![],
+        );
+        self.get_cached(&SpotifyRessourceType::AudioAnalysis, track_id, rb)
+            .await
+    }
+
+    /// Audio features of at most [AUDIO_FEATURES_BATCH_SIZE] tracks.
+    /// The tracks without features are left out
+    async fn audio_features_chunk(
+        &self,
+        track_ids: &[String],
+    ) -> SpotifyResult<Vec<AudioFeatures>> {
+        let r_type = SpotifyRessourceType::AudioFeature;
+        // A single id is requested on the audio feature endpoint
+        if let [id] = track_ids {
+            return match self.audio_features(id).await {
+                Ok(feature) => Ok(vec![feature]),
+                Err(SpotifyError::NotFound(_)) => Ok(vec![]),
+                Err(e) => Err(e),
+            };
         }
+        let rb = self.setup_url_request(&r_type, track_ids.to_vec(), None, None, None, vec![]);
+        let response = self.get::<AudioFeaturesResponse>(rb).await?;
+        let mut features = vec![];
+        for value in response.audio_features.into_iter().flatten() {
+            let body = value.to_string();
+            let feature = from_json::<AudioFeatures>(&body)?;
+            let () = self
+                .cache
+                .put(&r_type, &ResponseCache::key(&feature.id, None), &body);
+            features.push(feature);
+        }
+        Ok(features)
     }
-    pub async fn album_tracks(&self, album_id: String) -> Option<SpotifyAlbumTrackResult> {
+
+    /// Audio features of several tracks, in the same order as the ids.
+    /// Tracks without features are [None]
+    pub async fn audio_features_batch(
+        &self,
+        track_ids: &[String],
+    ) -> SpotifyResult<Vec<Option<AudioFeatures>>> {
+        let r_type = SpotifyRessourceType::AudioFeature;
+        let mut features = track_ids
+            .iter()
+            .map(|id| {
+                self.cache
+                    .get(&r_type, &ResponseCache::key(id, None))
+                    .map(|body| from_json::<AudioFeatures>(&body))
+                    .transpose()
+            })
+            .collect::<SpotifyResult<Vec<_>>>()?;
+        let missing = track_ids
+            .iter()
+            .zip(features.iter())
+            .filter(|(_, feature)| feature.is_none())
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        for chunk in missing.chunks(AUDIO_FEATURES_BATCH_SIZE) {
+            for feature in self.audio_features_chunk(chunk).await? {
+                track_ids
+                    .iter()
+                    .zip(features.iter_mut())
+                    .filter(|(id, _)| **id == feature.id)
+                    .for_each(|(_, slot)| *slot = Some(feature.clone()));
+            }
+        }
+        Ok(features)
+    }
+
+    pub fn album_tracks(&self, album_id: String) -> Paginator<'_, SpotifyAlbumTrackResult> {
         let rb = self.setup_url_request(
             &SpotifyRessourceType::AlbumTrack,
-            vec![album_id],
+            vec![album_id.clone()],
             None,
             Some(50),
             None,
             vec![],
         );
-        rb.send().await.ok()?.json().await.ok()
+        Paginator::cached(self, SpotifyRessourceType::AlbumTrack, &album_id, rb)
     }
     pub async fn artist_album(
         &self,
@@ -317,7 +710,7 @@ impl Spotify {
         limit: Option<u32>,
         market: Option<String>,
         offset: Option<u32>,
-    ) -> Option<ArtistAlbum> {
+    ) -> SpotifyResult<ArtistAlbum> {
         let rb = self.setup_url_request(
             &SpotifyRessourceType::ArtistAlbum,
             vec![artist_id],
@@ -326,13 +719,27 @@ impl Spotify {
             offset,
             included_genre,
         );
-        if let Ok(response) = rb.send().await {
-            response.json::<ArtistAlbum>().await.ok()
-        } else {
-            None
-        }
+        self.get(rb).await
+    }
+
+    /// Iterate over all the albums of an artist
+    pub fn artist_albums(
+        &self,
+        artist_id: String,
+        included_genre: Vec<SpotifyIncludeGroupe>,
+        market: Option<String>,
+    ) -> Paginator<'_, ArtistAlbum> {
+        let rb = self.setup_url_request(
+            &SpotifyRessourceType::ArtistAlbum,
+            vec![artist_id.clone()],
+            market,
+            Some(50),
+            None,
+            included_genre,
+        );
+        Paginator::cached(self, SpotifyRessourceType::ArtistAlbum, &artist_id, rb)
     }
-    pub async fn related_artists(&self, artist_id: &String) -> Option<Vec<Artist>> {
+    pub async fn related_artists(&self, artist_id: &String) -> SpotifyResult<Vec<Artist>> {
         let rb = self.setup_url_request(
             &SpotifyRessourceType::RelatedArtist,
             vec![artist_id.clone()],
@@ -341,37 +748,59 @@ impl Spotify {
             None,
             vec![],
         );
-        if let Ok(response) = rb.send().await {
-            let map = response
-                .json::<HashMap<SpotifySearchKey, Vec<Artist>>>()
-                .await
-                .ok()?;
-            map.into_iter().map(|(k, v)| v).find(|_| true)
-        } else {
-            None
-        }
+        let mut map = self
+            .get_cached::<HashMap<SpotifySearchKey, Vec<Artist>>>(
+                &SpotifyRessourceType::RelatedArtist,
+                artist_id,
+                rb,
+            )
+            .await?;
+        map.remove(&SpotifySearchKey::Artists)
+            .ok_or(SpotifyError::NotFound(None))
     }
 }
 
 impl Spotify {
-    pub async fn artist_lastest_album(&self, artist_id: &str) -> Option<AlbumItems> {
-        let mut album = self
-            .artist_album(
+    pub async fn artist_lastest_album(&self, artist_id: &str) -> SpotifyResult<AlbumItems> {
+        let albums = self
+            .artist_albums(
                 artist_id.into(),
                 vec![SpotifyIncludeGroupe::Album, SpotifyIncludeGroupe::Single],
-                Some(40),
                 None,
+            )
+            .collect()
+            .await?;
+        albums
+            .into_iter()
+            .max_by(|a, b| a.release_date.cmp(&b.release_date))
+            .ok_or(SpotifyError::NotFound(None))
+    }
+
+    pub async fn get_track_id(&self, track_name: &str) -> SpotifyResult<String> {
+        let result = self
+            .search(
+                track_name,
+                vec![SpotifySearchType::Track],
+                None,
+                Some(1),
                 Some(0),
+                None,
             )
             .await?;
-        album
+        let items = result
+            .get(&SpotifySearchKey::Tracks)
+            .ok_or(SpotifyError::NotFound(None))?;
+        items
             .items
-            .sort_by(|a, b| a.release_date.partial_cmp(&b.release_date).unwrap());
-        let items = album.items.remove(0);
-        Some(items)
+            .iter()
+            .find_map(|ssri| match ssri {
+                SpotifySearchResultItem::Track { id, .. } => Some(id.clone()),
+                _ => None,
+            })
+            .ok_or(SpotifyError::NotFound(None))
     }
 
-    pub async fn get_artist_id(&self, artist_name: String) -> Option<String> {
+    pub async fn get_artist_id(&self, artist_name: String) -> SpotifyResult<String> {
         let result = self
             .search(
                 artist_name.as_str(),
@@ -382,34 +811,23 @@ impl Spotify {
                 None,
             )
             .await?;
-        let items = result.get(&crate::libs::spotify::SpotifySearchKey::Artists)?;
-        let mut vec_artist_id = items
+        let items = result
+            .get(&crate::libs::spotify::SpotifySearchKey::Artists)
+            .ok_or(SpotifyError::NotFound(None))?;
+        items
             .items
             .iter()
-            .filter_map(|ssri| match ssri {
-                crate::libs::spotify::SpotifySearchResultItem::Artist {
-                    external_urls,
-                    followers,
-                    genres,
-                    href,
-                    id,
-                    images,
-                    name,
-                    popularity,
-                    artist_type,
-                    uri,
-                } => Some(id.clone()),
+            .find_map(|ssri| match ssri {
+                crate::libs::spotify::SpotifySearchResultItem::Artist { id, .. } => {
+                    Some(id.clone())
+                }
                 _ => None,
             })
-            .collect::<Vec<String>>();
-        if !vec_artist_id.is_empty() {
-            Some(vec_artist_id.remove(0))
-        } else {
-            None
-        }
+            .ok_or(SpotifyError::NotFound(None))
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
 pub enum SpotifyRessourceType {
     Track,
     Artist,
@@ -422,7 +840,51 @@ pub enum SpotifyRessourceType {
     AudioAnalysis,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+const HOUR: i64 = 3600;
+const DAY: i64 = 24 * HOUR;
+
+impl SpotifyRessourceType {
+    pub const ALL: [SpotifyRessourceType; 9] = [
+        Self::Track,
+        Self::Artist,
+        Self::ArtistAlbum,
+        Self::ArtistTopTrack,
+        Self::Album,
+        Self::AlbumTrack,
+        Self::AudioFeature,
+        Self::RelatedArtist,
+        Self::AudioAnalysis,
+    ];
+
+    /// Directory of the cached responses
+    pub fn cache_name(&self) -> &'static str {
+        match self {
+            Self::Track => "track",
+            Self::Artist => "artist",
+            Self::ArtistAlbum => "artist-album",
+            Self::ArtistTopTrack => "artist-top-track",
+            Self::Album => "album",
+            Self::AlbumTrack => "album-track",
+            Self::AudioFeature => "audio-feature",
+            Self::RelatedArtist => "related-artist",
+            Self::AudioAnalysis => "audio-analysis",
+        }
+    }
+
+    /// Seconds during which a cached response is used
+    pub fn cache_ttl(&self) -> i64 {
+        match self {
+            Self::Album | Self::AlbumTrack | Self::Track => 30 * DAY,
+            Self::AudioFeature | Self::AudioAnalysis => 365 * DAY,
+            Self::Artist | Self::RelatedArtist => 7 * DAY,
+            Self::ArtistTopTrack => DAY,
+            // Needed fresh to detect the new releases
+            Self::ArtistAlbum => 6 * HOUR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
 pub enum SpotifyIncludeGroupe {
     Single,
     Album,
@@ -430,6 +892,32 @@ pub enum SpotifyIncludeGroupe {
     Compilation,
 }
 
+impl SpotifyIncludeGroupe {
+    /// Display order of a discography
+    pub const ALL: [SpotifyIncludeGroupe; 4] = [
+        Self::Album,
+        Self::Single,
+        Self::Compilation,
+        Self::AppearsOn,
+    ];
+
+    /// Group of an album from its `album_group` field
+    pub fn from_album_group(album_group: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|group| group.to_string() == album_group)
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            SpotifyIncludeGroupe::Single => "Singles",
+            SpotifyIncludeGroupe::Album => "Albums",
+            SpotifyIncludeGroupe::AppearsOn => "Appears On",
+            SpotifyIncludeGroupe::Compilation => "Compilations",
+        }
+    }
+}
+
 impl Display for SpotifyIncludeGroupe {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
@@ -461,9 +949,9 @@ impl Display for SpotifySearchType {
 }
 #[derive(Debug, Deserialize)]
 pub struct Copyrights {
-    text: String,
+    pub(crate) text: String,
     #[serde(rename = "type")]
-    c_type: String,
+    pub(crate) c_type: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -486,7 +974,7 @@ pub struct Album {
     pub(crate) label: Option<Option<String>>,
     pub(crate) name: String,
     pub(crate) popularity: Option<Option<i32>>,
-    pub(crate) release_date: NaiveDate,
+    pub(crate) release_date: ReleaseDate,
     pub(crate) release_date_precision: String,
     pub(crate) total_tracks: u32,
     pub(crate) tracks: SpotifyAlbumTrackResult,
@@ -497,6 +985,86 @@ pub struct Album {
     pub(crate) uri: String,
 }
 
+impl Album {
+    pub fn artists_name(&self) -> String {
+        self.artists
+            .iter()
+            .map(|artist| artist.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn runtime_ms(&self) -> u64 {
+        self.tracks
+            .items
+            .iter()
+            .map(|track| track.duration_ms)
+            .sum()
+    }
+
+    pub(crate) async fn dynamic_image(&self) -> Option<DynamicImage> {
+        let url = self.images.get(0)?.get("url")?.as_str()?;
+        util::donwload_image(url).await
+    }
+
+    pub fn default_format(&self) -> String {
+        let mut s = String::new();
+        s.push_str(format!("****   Album Name    : {}\n", self.name).as_str());
+        s.push_str(format!("****   Album ID      : {}\n", self.id).as_str());
+        s.push_str(format!("****   Artists       : {}\n", self.artists_name()).as_str());
+        s.push_str(format!("****   Release Date  : {}\n", self.release_date).as_str());
+        s.push_str(format!("****   Album Type    : {}\n", self.album_type).as_str());
+        if let Some(Some(label)) = &self.label {
+            s.push_str(format!("****   Label         : {}\n", label).as_str());
+        }
+        if let Some(upc) = self
+            .external_ids
+            .as_ref()
+            .and_then(|ids| ids.as_ref())
+            .and_then(|ids| ids.get("upc"))
+        {
+            s.push_str(format!("****   UPC           : {}\n", upc).as_str());
+        }
+        if let Some(Some(genres)) = &self.genres {
+            if !genres.is_empty() {
+                let genres = genres.join("\n                     : ");
+                s.push_str(format!("****   Genres        : {}\n", genres).as_str());
+            }
+        }
+        if let Some(Some(copyrights)) = &self.copyrights {
+            if !copyrights.is_empty() {
+                let copyrights = copyrights
+                    .iter()
+                    .map(|copyright| copyright.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n                     : ");
+                s.push_str(format!("****   Copyrights    : {}\n", copyrights).as_str());
+            }
+        }
+        s.push_str(
+            format!(
+                "****   Runtime       : {}\n",
+                util::format_duration(self.runtime_ms())
+            )
+            .as_str(),
+        );
+        s.push_str(format!("****   Tracks        : {}\n", self.total_tracks).as_str());
+        for track in self.tracks.items.iter() {
+            s.push_str(
+                format!(
+                    "       {:>2}-{:02}  {:<50} {:>8}\n",
+                    track.disc_number,
+                    track.track_number,
+                    track.name,
+                    util::format_duration(track.duration_ms)
+                )
+                .as_str(),
+            );
+        }
+        s
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Artist {
     pub(crate) external_urls: HashMap<String, String>,
@@ -542,6 +1110,8 @@ pub struct ArtistAlbum {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AlbumItems {
+    /// Missing outside of an artist discography
+    #[serde(default)]
     pub(crate) album_group: String,
     pub(crate) album_type: String,
     pub(crate) artists: Vec<Value>,
@@ -552,14 +1122,49 @@ pub struct AlbumItems {
     pub(crate) id: String,
     pub(crate) images: Vec<HashMap<String, Value>>,
     pub(crate) name: String,
-    pub(crate) release_date: NaiveDate,
+    pub(crate) release_date: ReleaseDate,
     pub(crate) release_date_precision: String,
-    pub(crate) total_tracks: u8,
+    pub(crate) total_tracks: u32,
     #[serde(rename = "type")]
     pub(crate) r#type: String,
     pub(crate) uri: String,
 }
 
+impl AlbumItems {
+    pub fn artists_name(&self) -> String {
+        self.artists
+            .iter()
+            .filter_map(|artist| artist.get("name")?.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn artist_ids(&self) -> Vec<String> {
+        self.artists
+            .iter()
+            .filter_map(|artist| artist.get("id")?.as_str().map(String::from))
+            .collect()
+    }
+
+    pub fn group(&self) -> Option<SpotifyIncludeGroupe> {
+        SpotifyIncludeGroupe::from_album_group(&self.album_group)
+    }
+
+    /// Regional releases of the same album share these
+    pub fn edition_key(&self) -> (String, String, u32) {
+        (
+            self.album_group.clone(),
+            self.name.to_lowercase(),
+            self.total_tracks,
+        )
+    }
+
+    pub(crate) async fn dynamic_image(&self) -> Option<DynamicImage> {
+        let url = self.images.get(0)?.get("url")?.as_str()?;
+        util::donwload_image(url).await
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq, Hash)]
 #[serde(try_from = "String")]
 pub enum SpotifySearchKey {
@@ -606,6 +1211,24 @@ pub struct SpotifySearchResult {
     pub(crate) total: u32,
 }
 
+/// Response of the browse new releases endpoint
+#[derive(Debug, Deserialize)]
+pub struct NewReleases {
+    albums: ArtistAlbum,
+}
+
+impl Page for NewReleases {
+    type Item = AlbumItems;
+
+    fn next_page(&self) -> Option<String> {
+        self.albums.next_page()
+    }
+
+    fn into_items(self) -> Vec<Self::Item> {
+        self.albums.into_items()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SpotifyAlbumTrackResult {
     pub(crate) href: String,
@@ -616,6 +1239,301 @@ pub struct SpotifyAlbumTrackResult {
     pub(crate) previous: Option<String>,
     pub(crate) total: u32,
 }
+impl Page for ArtistAlbum {
+    type Item = AlbumItems;
+
+    fn next_page(&self) -> Option<String> {
+        self.next.clone()
+    }
+
+    fn into_items(self) -> Vec<Self::Item> {
+        self.items
+    }
+}
+
+impl Page for SpotifySearchResult {
+    type Item = SpotifySearchResultItem;
+
+    fn next_page(&self) -> Option<String> {
+        self.next.clone()
+    }
+
+    fn into_items(self) -> Vec<Self::Item> {
+        self.items
+    }
+}
+
+impl Page for SpotifyAlbumTrackResult {
+    type Item = TrackAlbum;
+
+    fn next_page(&self) -> Option<String> {
+        self.next.clone()
+    }
+
+    fn into_items(self) -> Vec<Self::Item> {
+        self.items
+    }
+}
+
+/// Search response restricted to a single ressource type
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "HashMap<SpotifySearchKey, SpotifySearchResult>")]
+pub struct SearchPage(SpotifySearchResult);
+
+impl TryFrom<HashMap<SpotifySearchKey, SpotifySearchResult>> for SearchPage {
+    type Error = SpotifyKeyError;
+
+    fn try_from(
+        value: HashMap<SpotifySearchKey, SpotifySearchResult>,
+    ) -> Result<Self, Self::Error> {
+        match value.len() {
+            1 => value
+                .into_values()
+                .next()
+                .map(SearchPage)
+                .ok_or(SpotifyKeyError),
+            _ => Err(SpotifyKeyError),
+        }
+    }
+}
+
+impl Page for SearchPage {
+    type Item = SpotifySearchResultItem;
+
+    fn next_page(&self) -> Option<String> {
+        self.0.next_page()
+    }
+
+    fn into_items(self) -> Vec<Self::Item> {
+        self.0.into_items()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Track {
+    pub(crate) album: SpotifySearchTrackAlbum,
+    pub(crate) artists: Vec<SpotifySearchAlbumArtist>,
+    #[serde(default, with = "serde_with::rust::double_option")]
+    pub(crate) available_markets: Option<Option<Vec<String>>>,
+    pub(crate) disc_number: u16,
+    pub(crate) duration_ms: u64,
+    pub(crate) explicit: bool,
+    pub(crate) external_ids: HashMap<String, String>,
+    pub(crate) external_urls: HashMap<String, String>,
+    pub(crate) href: String,
+    pub(crate) id: String,
+    pub(crate) is_local: bool,
+    pub(crate) name: String,
+    pub(crate) popularity: u32,
+    pub(crate) preview_url: Option<String>,
+    pub(crate) track_number: u16,
+    #[serde(rename = "type")]
+    pub(crate) t_type: String,
+    pub(crate) uri: String,
+}
+
+impl Track {
+    pub fn duration(&self) -> String {
+        util::format_duration(self.duration_ms)
+    }
+
+    pub fn isrc(&self) -> Option<&String> {
+        self.external_ids.get("isrc")
+    }
+
+    pub fn default_format(&self) -> String {
+        let mut s = String::new();
+        s.push_str(format!("****   Song Name      : {}\n", self.name).as_str());
+        s.push_str(format!("****   Song ID        : {}\n", self.id).as_str());
+        s.push_str(format!("****   Artists        : {}\n", self.artists_name()).as_str());
+        s.push_str(
+            format!(
+                "****   Album          : {} ({})\n",
+                self.album.name, self.album.release_date
+            )
+            .as_str(),
+        );
+        s.push_str(
+            format!(
+                "****   Position       : Disc {} - Track {}/{}\n",
+                self.disc_number, self.track_number, self.album.total_tracks
+            )
+            .as_str(),
+        );
+        s.push_str(
+            format!(
+                "****   Explicit       : {}\n",
+                if self.explicit { "Yes" } else { "No" }
+            )
+            .as_str(),
+        );
+        if let Some(isrc) = self.isrc() {
+            s.push_str(format!("****   ISRC           : {}\n", isrc).as_str());
+        }
+        s.push_str(format!("****   Duration       : {}\n", self.duration()).as_str());
+        s.push_str(format!("****   Popularity     : {}\n", self.popularity).as_str());
+        s
+    }
+
+    pub fn artists_name(&self) -> String {
+        self.artists
+            .iter()
+            .map(|artist| artist.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub(crate) async fn album_image(&self) -> Option<DynamicImage> {
+        let url = self.album.images.get(0)?.get("url")?.as_str()?;
+        util::donwload_image(url).await
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackSearch {
+    tracks: TrackSearchPage,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackSearchPage {
+    items: Vec<Track>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopTracks {
+    tracks: Vec<Track>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AudioFeaturesResponse {
+    audio_features: Vec<Option<Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArtistsResponse {
+    artists: Vec<Option<Value>>,
+}
+
+const PITCH_CLASSES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+fn key_name(key: i32, mode: i32) -> Option<String> {
+    let pitch = PITCH_CLASSES.get(usize::try_from(key).ok()?)?;
+    Some(format!(
+        "{} {}",
+        pitch,
+        if mode == 1 { "major" } else { "minor" }
+    ))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioFeatures {
+    pub(crate) acousticness: f64,
+    pub(crate) analysis_url: String,
+    pub(crate) danceability: f64,
+    pub(crate) duration_ms: u64,
+    pub(crate) energy: f64,
+    pub(crate) id: String,
+    pub(crate) instrumentalness: f64,
+    /// Pitch class of the track, -1 if no key was detected
+    pub(crate) key: i32,
+    pub(crate) liveness: f64,
+    pub(crate) loudness: f64,
+    /// 1 for major, 0 for minor
+    pub(crate) mode: i32,
+    pub(crate) speechiness: f64,
+    pub(crate) tempo: f64,
+    pub(crate) time_signature: i32,
+    pub(crate) track_href: String,
+    #[serde(rename = "type")]
+    pub(crate) f_type: String,
+    pub(crate) uri: String,
+    pub(crate) valence: f64,
+}
+
+impl AudioFeatures {
+    pub fn is_major(&self) -> bool {
+        self.mode == 1
+    }
+
+    /// Key in the initial key tag notation (ex: C, F#m)
+    pub fn initial_key(&self) -> Option<String> {
+        let pitch = PITCH_CLASSES.get(usize::try_from(self.key).ok()?)?;
+        Some(format!(
+            "{}{}",
+            pitch,
+            if self.is_major() { "" } else { "m" }
+        ))
+    }
+
+    /// Key in plain text (ex: C major, F# minor)
+    pub fn key_name(&self) -> Option<String> {
+        key_name(self.key, self.mode)
+    }
+
+    /// Tempo rounded for the bpm tag
+    pub fn bpm(&self) -> u16 {
+        self.tempo.round() as u16
+    }
+
+    pub fn default_format(&self) -> String {
+        let mut s = String::new();
+        s.push_str(format!("****   Tempo          : {:.1} BPM\n", self.tempo).as_str());
+        s.push_str(
+            format!(
+                "****   Key            : {}\n",
+                self.key_name().unwrap_or_else(|| "Unknown".into())
+            )
+            .as_str(),
+        );
+        s.push_str(format!("****   Time Signature : {}/4\n", self.time_signature).as_str());
+        s.push_str(format!("****   Energy         : {:.3}\n", self.energy).as_str());
+        s.push_str(format!("****   Danceability   : {:.3}\n", self.danceability).as_str());
+        s.push_str(format!("****   Loudness       : {:.1} dB\n", self.loudness).as_str());
+        s
+    }
+}
+
+/// Bar, beat or tatum of the audio analysis, times are in seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeInterval {
+    pub(crate) start: f64,
+    pub(crate) duration: f64,
+    pub(crate) confidence: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisSection {
+    pub(crate) start: f64,
+    pub(crate) duration: f64,
+    pub(crate) confidence: f64,
+    pub(crate) loudness: f64,
+    pub(crate) tempo: f64,
+    pub(crate) tempo_confidence: f64,
+    pub(crate) key: i32,
+    pub(crate) key_confidence: f64,
+    pub(crate) mode: i32,
+    pub(crate) mode_confidence: f64,
+    pub(crate) time_signature: i32,
+    pub(crate) time_signature_confidence: f64,
+}
+
+impl AnalysisSection {
+    /// Key in plain text (ex: C major, F# minor)
+    pub fn key_name(&self) -> Option<String> {
+        key_name(self.key, self.mode)
+    }
+}
+
+/// Audio analysis without the segments and tatums which are too fine grained to be of use
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioAnalysis {
+    pub(crate) bars: Vec<TimeInterval>,
+    pub(crate) beats: Vec<TimeInterval>,
+    pub(crate) sections: Vec<AnalysisSection>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TrackAlbum {
     pub(crate) artists: Vec<SpotifySearchAlbumArtist>,
@@ -629,7 +1547,7 @@ pub struct TrackAlbum {
     pub(crate) id: String,
     pub(crate) is_local: bool,
     pub(crate) name: String,
-    pub(crate) preview_url: String,
+    pub(crate) preview_url: Option<String>,
     pub(crate) track_number: u16,
     #[serde(rename = "type")]
     pub(crate) _type: String,
@@ -683,7 +1601,7 @@ pub enum SpotifySearchResultItem {
         id: String,
         images: Vec<HashMap<String, Value>>,
         name: String,
-        release_date: String,
+        release_date: ReleaseDate,
         release_date_precision: String,
         total_tracks: u32,
         #[serde(rename = "type")]
@@ -693,21 +1611,21 @@ pub enum SpotifySearchResultItem {
 }
 #[derive(Debug, Deserialize)]
 pub struct SpotifySearchTrackAlbum {
-    album_type: String,
-    artists: Vec<SpotifySearchAlbumArtist>,
+    pub(crate) album_type: String,
+    pub(crate) artists: Vec<SpotifySearchAlbumArtist>,
     #[serde(default, with = "serde_with::rust::double_option")]
-    available_markets: Option<Option<Vec<String>>>,
-    external_urls: HashMap<String, String>,
-    href: String,
-    id: String,
-    images: Vec<HashMap<String, Value>>,
-    name: String,
-    release_date: String,
-    release_date_precision: String,
-    total_tracks: u32,
+    pub(crate) available_markets: Option<Option<Vec<String>>>,
+    pub(crate) external_urls: HashMap<String, String>,
+    pub(crate) href: String,
+    pub(crate) id: String,
+    pub(crate) images: Vec<HashMap<String, Value>>,
+    pub(crate) name: String,
+    pub(crate) release_date: ReleaseDate,
+    pub(crate) release_date_precision: String,
+    pub(crate) total_tracks: u32,
     #[serde(rename = "type")]
-    a_type: String,
-    uri: String,
+    pub(crate) a_type: String,
+    pub(crate) uri: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -722,6 +1640,78 @@ pub struct SpotifySearchAlbumArtist {
 }
 
 impl SpotifySearchResultItem {
+    pub fn id(&self) -> &str {
+        match self {
+            SpotifySearchResultItem::Track { id, .. }
+            | SpotifySearchResultItem::Artist { id, .. }
+            | SpotifySearchResultItem::Album { id, .. } => id,
+        }
+    }
+
+    pub(crate) async fn dynamic_image(&self) -> Option<DynamicImage> {
+        let images = match self {
+            SpotifySearchResultItem::Track { album, .. } => &album.images,
+            SpotifySearchResultItem::Artist { images, .. }
+            | SpotifySearchResultItem::Album { images, .. } => images,
+        };
+        let url = images.last()?.get("url")?.as_str()?;
+        util::donwload_image(url).await
+    }
+
+    /// One line summary to tell apart the results of a search
+    pub fn candidate_format(&self) -> String {
+        let names = |artists: &Vec<SpotifySearchAlbumArtist>| {
+            artists
+                .iter()
+                .map(|artist| artist.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            SpotifySearchResultItem::Track {
+                album,
+                artists,
+                name,
+                ..
+            } => format!(
+                "{} - {} ({}, {}, track)",
+                name,
+                names(artists),
+                album.name,
+                album.release_date.year()
+            ),
+            SpotifySearchResultItem::Artist {
+                genres,
+                name,
+                popularity,
+                ..
+            } => format!(
+                "{} ({}, popularity {}, artist)",
+                name,
+                match genres.is_empty() {
+                    true => "no genre".to_string(),
+                    false => genres.join(", "),
+                },
+                popularity
+            ),
+            SpotifySearchResultItem::Album {
+                album_type,
+                artists,
+                name,
+                release_date,
+                total_tracks,
+                ..
+            } => format!(
+                "{} - {} ({}, {}, {} tracks)",
+                name,
+                names(artists),
+                release_date.year(),
+                album_type,
+                total_tracks
+            ),
+        }
+    }
+
     pub fn default_format(&self) -> String {
         let mut s = String::new();
         match self {
@@ -0,0 +1,228 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::{fmt::Display, process::exit};
+
+/// Length of a base62 spotify id
+const SPOTIFY_ID_LEN: usize = 22;
+
+/// Ressource type named by a spotify url or uri
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpotifyIdType {
+    Album,
+    Artist,
+    Track,
+    Playlist,
+    Show,
+    Episode,
+}
+
+impl SpotifyIdType {
+    const ALL: [SpotifyIdType; 6] = [
+        Self::Album,
+        Self::Artist,
+        Self::Track,
+        Self::Playlist,
+        Self::Show,
+        Self::Episode,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpotifyIdType::Album => "album",
+            SpotifyIdType::Artist => "artist",
+            SpotifyIdType::Track => "track",
+            SpotifyIdType::Playlist => "playlist",
+            SpotifyIdType::Show => "show",
+            SpotifyIdType::Episode => "episode",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+impl Display for SpotifyIdType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug)]
+pub enum SpotifyIdError {
+    /// The input isn't a spotify id, url or uri
+    Invalid(String),
+    /// The url or uri points to another ressource type
+    WrongType {
+        input: String,
+        expected: SpotifyIdType,
+        found: SpotifyIdType,
+    },
+}
+
+impl Display for SpotifyIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpotifyIdError::Invalid(input) => {
+                write!(f, "Not a spotify id, url or uri : {}", input)
+            }
+            SpotifyIdError::WrongType {
+                input,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Expected a spotify {} but got a {} : {}",
+                expected, found, input
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpotifyIdError {}
+
+impl SpotifyIdError {
+    pub fn exit(&self) -> ! {
+        eprintln!("{}", self);
+        exit(1)
+    }
+}
+
+fn is_base62_id(id: &str) -> bool {
+    id.len() == SPOTIFY_ID_LEN && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Split an url (`https://open.spotify.com/intl-fr/album/<id>?si=...`) or an uri (`spotify:album:<id>`)
+/// into its ressource type and id
+fn split_link(input: &str) -> Option<(&str, &str)> {
+    if let Some(uri) = input.strip_prefix("spotify:") {
+        return uri.split_once(':');
+    }
+    let url = input
+        .strip_prefix("https://")
+        .or_else(|| input.strip_prefix("http://"))
+        .unwrap_or(input);
+    let path = url.strip_prefix("open.spotify.com/")?;
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let mut segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty() && !segment.starts_with("intl-"));
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some(kind), Some(id), None) => Some((kind, id)),
+        _ => None,
+    }
+}
+
+/// Extract the id of a ressource of type `expected` from a bare id, an url or an uri
+pub fn parse(input: &str, expected: SpotifyIdType) -> Result<String, SpotifyIdError> {
+    let input = input.trim();
+    if is_base62_id(input) {
+        return Ok(input.to_string());
+    }
+    let invalid = || SpotifyIdError::Invalid(input.to_string());
+    let (kind, id) = split_link(input).ok_or_else(invalid)?;
+    let found = SpotifyIdType::from_name(kind).ok_or_else(invalid)?;
+    if !is_base62_id(id) {
+        return Err(invalid());
+    }
+    match found == expected {
+        true => Ok(id.to_string()),
+        false => Err(SpotifyIdError::WrongType {
+            input: input.to_string(),
+            expected,
+            found,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, split_link, SpotifyIdError, SpotifyIdType};
+
+    const ID: &str = "4tZwfgrHOc3mvqYlEYSvVN";
+
+    #[test]
+    fn bare_id() {
+        assert_eq!(parse(ID, SpotifyIdType::Artist).unwrap(), ID);
+        assert_eq!(
+            parse(" 4tZwfgrHOc3mvqYlEYSvVN\n", SpotifyIdType::Album).unwrap(),
+            ID
+        );
+    }
+
+    #[test]
+    fn url() {
+        let url = "https://open.spotify.com/intl-fr/artist/4tZwfgrHOc3mvqYlEYSvVN?si=a1b2c3d4";
+        assert_eq!(split_link(url), Some(("artist", ID)));
+        assert_eq!(parse(url, SpotifyIdType::Artist).unwrap(), ID);
+        let url = "open.spotify.com/artist/4tZwfgrHOc3mvqYlEYSvVN/";
+        assert_eq!(parse(url, SpotifyIdType::Artist).unwrap(), ID);
+        let url = "https://open.spotify.com/artist/4tZwfgrHOc3mvqYlEYSvVN#top";
+        assert_eq!(parse(url, SpotifyIdType::Artist).unwrap(), ID);
+    }
+
+    #[test]
+    fn uri() {
+        let uri = "spotify:artist:4tZwfgrHOc3mvqYlEYSvVN";
+        assert_eq!(split_link(uri), Some(("artist", ID)));
+        assert_eq!(parse(uri, SpotifyIdType::Artist).unwrap(), ID);
+    }
+
+    #[test]
+    fn wrong_type() {
+        let uri = "spotify:album:4tZwfgrHOc3mvqYlEYSvVN";
+        assert!(matches!(
+            parse(uri, SpotifyIdType::Artist),
+            Err(SpotifyIdError::WrongType {
+                expected: SpotifyIdType::Artist,
+                found: SpotifyIdType::Album,
+                ..
+            })
+        ));
+        let url = "https://open.spotify.com/track/4tZwfgrHOc3mvqYlEYSvVN";
+        assert!(matches!(
+            parse(url, SpotifyIdType::Album),
+            Err(SpotifyIdError::WrongType { .. })
+        ));
+    }
+
+    #[test]
+    fn garbage() {
+        for input in [
+            "",
+            "daft punk",
+            "4tZwfgrHOc3mvqYlEYSvV",
+            "4tZwfgrHOc3mvqYlEYSvVN!",
+            "spotify:artist",
+            "spotify:artist:short",
+            "spotify:band:4tZwfgrHOc3mvqYlEYSvVN",
+            "https://example.com/artist/4tZwfgrHOc3mvqYlEYSvVN",
+            "https://open.spotify.com/artist",
+            "https://open.spotify.com/artist/4tZwfgrHOc3mvqYlEYSvVN/albums",
+        ] {
+            assert!(
+                matches!(
+                    parse(input, SpotifyIdType::Artist),
+                    Err(SpotifyIdError::Invalid(_))
+                ),
+                "{}",
+                input
+            );
+        }
+    }
+}
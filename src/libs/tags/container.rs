@@ -0,0 +1,199 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use super::TagResult;
+
+/// Number of bytes needed to recognize a container
+const MAGIC_LEN: usize = 12;
+const ID3_HEADER_LEN: u64 = 10;
+
+/// Audio container of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Mp3,
+    Flac,
+    Ogg,
+    Mp4,
+    Wav,
+    Aiff,
+}
+
+impl Display for Container {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Container::Mp3 => "mp3",
+            Container::Flac => "flac",
+            Container::Ogg => "ogg",
+            Container::Mp4 => "mp4",
+            Container::Wav => "wav",
+            Container::Aiff => "aiff",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Container {
+    /// Recognize the container from the first bytes of a file
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [b'f', b'L', b'a', b'C', ..] => Some(Self::Flac),
+            [b'O', b'g', b'g', b'S', ..] => Some(Self::Ogg),
+            [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some(Self::Mp4),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some(Self::Wav),
+            [b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'F' | b'C', ..] => {
+                Some(Self::Aiff)
+            }
+            // Mpeg audio frame sync, the layer bits exclude the AAC ADTS frames
+            [0xFF, second, ..] if second & 0xE0 == 0xE0 && second & 0x06 != 0 => Some(Self::Mp3),
+            _ => None,
+        }
+    }
+
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "mp3" => Some(Self::Mp3),
+            "flac" => Some(Self::Flac),
+            "ogg" | "oga" | "opus" => Some(Self::Ogg),
+            "m4a" | "mp4" | "aac" | "alac" => Some(Self::Mp4),
+            "wav" | "wave" => Some(Self::Wav),
+            "aif" | "aiff" | "aifc" => Some(Self::Aiff),
+            _ => None,
+        }
+    }
+
+    /// Recognize the container from the content of a file, skipping a leading ID3v2 tag
+    pub fn sniff<P: AsRef<Path>>(path: P) -> TagResult<Option<Self>> {
+        let mut file = File::open(path.as_ref())?;
+        let mut magic = Vec::with_capacity(MAGIC_LEN);
+        let _ = (&mut file).take(MAGIC_LEN as u64).read_to_end(&mut magic)?;
+        let mut id3 = false;
+        if let [b'I', b'D', b'3', _, _, flags, s0, s1, s2, s3, ..] = magic[..] {
+            id3 = true;
+            let size = [s0, s1, s2, s3]
+                .iter()
+                .fold(0u64, |size, byte| (size << 7) | (*byte & 0x7F) as u64);
+            let footer = if flags & 0x10 != 0 { ID3_HEADER_LEN } else { 0 };
+            let _ = file.seek(SeekFrom::Start(ID3_HEADER_LEN + size + footer))?;
+            magic.clear();
+            let _ = (&mut file).take(MAGIC_LEN as u64).read_to_end(&mut magic)?;
+        }
+        // An id3 tag without any recognized audio after it is still a mp3
+        Ok(Self::from_magic(&magic).or_else(|| id3.then_some(Self::Mp3)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Container;
+    use crate::{commands::create_m3u::default_extensions, libs::tags::tests::scratch_dir};
+
+    #[test]
+    fn default_extensions_are_containers() {
+        for extension in default_extensions() {
+            let file = format!("song.{}", extension.to_uppercase());
+            assert!(Container::from_extension(&file).is_some(), "{}", file);
+        }
+    }
+
+    #[test]
+    fn magics() {
+        assert_eq!(
+            Container::from_magic(b"fLaC\0\0\0\x22"),
+            Some(Container::Flac)
+        );
+        assert_eq!(Container::from_magic(b"OggS\0\x02"), Some(Container::Ogg));
+        assert_eq!(
+            Container::from_magic(b"\0\0\0\x20ftypM4A "),
+            Some(Container::Mp4)
+        );
+        assert_eq!(
+            Container::from_magic(b"RIFF\x24\0\0\0WAVE"),
+            Some(Container::Wav)
+        );
+        assert_eq!(
+            Container::from_magic(b"FORM\0\0\0\x2eAIFF"),
+            Some(Container::Aiff)
+        );
+        assert_eq!(
+            Container::from_magic(b"FORM\0\0\0\x2eAIFC"),
+            Some(Container::Aiff)
+        );
+        // Mpeg 1 layer 3 and mpeg 2 layer 3 frame headers
+        assert_eq!(
+            Container::from_magic(&[0xFF, 0xFB, 0x90]),
+            Some(Container::Mp3)
+        );
+        assert_eq!(
+            Container::from_magic(&[0xFF, 0xF3, 0x90]),
+            Some(Container::Mp3)
+        );
+    }
+
+    #[test]
+    fn unknown_magics() {
+        // AAC ADTS frame headers, mpeg 4 and mpeg 2
+        assert_eq!(Container::from_magic(&[0xFF, 0xF1, 0x50, 0x80]), None);
+        assert_eq!(Container::from_magic(&[0xFF, 0xF9, 0x50, 0x80]), None);
+        assert_eq!(Container::from_magic(b"RIFF\x24\0\0\0AVI "), None);
+        assert_eq!(Container::from_magic(b"FORM\0\0\0\x2e8SVX"), None);
+        assert_eq!(Container::from_magic(b"garbage, not a song"), None);
+        assert_eq!(Container::from_magic(b"fLa"), None);
+        assert_eq!(Container::from_magic(b""), None);
+    }
+
+    #[test]
+    fn sniff_after_id3() {
+        let directory = scratch_dir("container_sniff");
+        // Id3v2 header with a 300 bytes tag, the size is a 7 bits per byte integer
+        let mut tagged = b"ID3\x04\0\0\0\0\x02\x2c".to_vec();
+        tagged.extend(vec![0; 300]);
+
+        // The content is sniffed, not the extension
+        let flac = directory.join("song.mp3");
+        let () = std::fs::write(&flac, [tagged.as_slice(), b"fLaC\0\0\0\x22"].concat()).unwrap();
+        assert_eq!(Container::sniff(&flac).unwrap(), Some(Container::Flac));
+
+        let mp3 = directory.join("song.flac");
+        let () = std::fs::write(&mp3, [tagged.as_slice(), &[0xFF, 0xFB, 0x90]].concat()).unwrap();
+        assert_eq!(Container::sniff(&mp3).unwrap(), Some(Container::Mp3));
+
+        // A tag without any recognized audio after it
+        let tag_only = directory.join("tag_only");
+        let () = std::fs::write(&tag_only, &tagged).unwrap();
+        assert_eq!(Container::sniff(&tag_only).unwrap(), Some(Container::Mp3));
+    }
+
+    #[test]
+    fn sniff_unknown() {
+        let directory = scratch_dir("container_sniff_unknown");
+        let adts = directory.join("song.aac");
+        let () = std::fs::write(&adts, [0xFF, 0xF1, 0x50, 0x80, 0x2E, 0x7F, 0xFC]).unwrap();
+        assert_eq!(Container::sniff(&adts).unwrap(), None);
+        let garbage = directory.join("song.mp3");
+        let () = std::fs::write(&garbage, b"not a song at all").unwrap();
+        assert_eq!(Container::sniff(&garbage).unwrap(), None);
+        assert!(Container::sniff(directory.join("missing.mp3")).is_err());
+    }
+}
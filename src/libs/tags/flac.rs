@@ -0,0 +1,259 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::path::Path;
+
+use super::{vorbis::VorbisComment, write_atomic, TagError, TagResult};
+
+const FLAC_MAGIC: &[u8; 4] = b"fLaC";
+const VORBIS_COMMENT_BLOCK: u8 = 4;
+const PADDING_BLOCK: u8 = 1;
+const MAX_BLOCK_LEN: usize = 0xFF_FFFF;
+
+struct MetadataBlock {
+    block_type: u8,
+    data: Vec<u8>,
+}
+
+/// Flac file split between its metadata blocks and its audio frames
+pub struct FlacFile {
+    blocks: Vec<MetadataBlock>,
+    frames: Vec<u8>,
+}
+
+impl FlacFile {
+    pub fn parse(bytes: Vec<u8>) -> TagResult<Self> {
+        if !bytes.starts_with(FLAC_MAGIC) {
+            return Err(TagError::Malformed("missing fLaC marker"));
+        }
+        let mut blocks = vec![];
+        let mut offset = FLAC_MAGIC.len();
+        loop {
+            let header = bytes
+                .get(offset..offset + 4)
+                .ok_or(TagError::Malformed("truncated metadata block"))?;
+            let is_last = header[0] & 0x80 != 0;
+            let block_type = header[0] & 0x7F;
+            let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+            let data = bytes
+                .get(offset + 4..offset + 4 + len)
+                .ok_or(TagError::Malformed("truncated metadata block"))?
+                .to_vec();
+            offset += 4 + len;
+            blocks.push(MetadataBlock { block_type, data });
+            if is_last {
+                break;
+            }
+        }
+        let frames = bytes[offset..].to_vec();
+        Ok(Self { blocks, frames })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> TagResult<Self> {
+        Self::parse(std::fs::read(path)?)
+    }
+
+    pub fn vorbis_comment(&self) -> TagResult<VorbisComment> {
+        match self
+            .blocks
+            .iter()
+            .find(|block| block.block_type == VORBIS_COMMENT_BLOCK)
+        {
+            Some(block) => VorbisComment::parse(&block.data),
+            None => Ok(VorbisComment::default()),
+        }
+    }
+
+    /// Replace the vorbis comment, the padding absorbs the size difference when possible
+    pub fn set_vorbis_comment(&mut self, comment: &VorbisComment) -> TagResult<()> {
+        let data = comment.to_bytes();
+        if data.len() > MAX_BLOCK_LEN {
+            return Err(TagError::Unsupported("vorbis comment too large"));
+        }
+        let old_len = match self
+            .blocks
+            .iter_mut()
+            .find(|block| block.block_type == VORBIS_COMMENT_BLOCK)
+        {
+            Some(block) => std::mem::replace(&mut block.data, data).len(),
+            None => {
+                // Streaminfo must stay the first block
+                let index = 1.min(self.blocks.len());
+                self.blocks.insert(
+                    index,
+                    MetadataBlock {
+                        block_type: VORBIS_COMMENT_BLOCK,
+                        data,
+                    },
+                );
+                0
+            }
+        };
+        let new_len = self
+            .blocks
+            .iter()
+            .find(|block| block.block_type == VORBIS_COMMENT_BLOCK)
+            .map(|block| block.data.len())
+            .unwrap_or(0);
+        if let Some(padding) = self
+            .blocks
+            .iter_mut()
+            .find(|block| block.block_type == PADDING_BLOCK)
+        {
+            let available = padding.data.len() + old_len;
+            if new_len <= available {
+                padding.data = vec![0; available - new_len];
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = FLAC_MAGIC.to_vec();
+        for (index, block) in self.blocks.iter().enumerate() {
+            let is_last = index + 1 == self.blocks.len();
+            let len = (block.data.len() as u32).to_be_bytes();
+            bytes.push(block.block_type | if is_last { 0x80 } else { 0 });
+            bytes.extend_from_slice(&len[1..]);
+            bytes.extend_from_slice(&block.data);
+        }
+        bytes.extend_from_slice(&self.frames);
+        bytes
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> TagResult<()> {
+        write_atomic(path, &self.to_bytes())
+    }
+}
+
+/// Set a field of the flac file vorbis comment
+pub fn set_comment<P: AsRef<Path>>(path: P, key: &str, value: &str) -> TagResult<()> {
+    let mut flac = FlacFile::from_path(&path)?;
+    let mut comment = flac.vorbis_comment()?;
+    let () = comment.set(key, value);
+    let () = flac.set_vorbis_comment(&comment)?;
+    flac.write(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_comment, FlacFile, FLAC_MAGIC, PADDING_BLOCK, VORBIS_COMMENT_BLOCK};
+    use crate::libs::tags::{tests::scratch_dir, vorbis::VorbisComment};
+
+    const STREAMINFO_BLOCK: u8 = 0;
+    const PADDING_LEN: usize = 256;
+    const FRAMES: &[u8] = b"\xFF\xF8audio frames";
+
+    /// Streaminfo and padding blocks followed by the frames
+    fn flac_bytes() -> Vec<u8> {
+        let mut bytes = FLAC_MAGIC.to_vec();
+        bytes.extend_from_slice(&[STREAMINFO_BLOCK, 0, 0, 34]);
+        bytes.extend_from_slice(&[0x11; 34]);
+        bytes.extend_from_slice(&[PADDING_BLOCK | 0x80, 0, 1, 0]);
+        bytes.extend_from_slice(&[0; PADDING_LEN]);
+        bytes.extend_from_slice(FRAMES);
+        bytes
+    }
+
+    /// Types of the blocks and whether they are flagged as the last one
+    fn block_headers(bytes: &[u8]) -> Vec<(u8, bool)> {
+        let mut headers = vec![];
+        let mut offset = FLAC_MAGIC.len();
+        loop {
+            let header = &bytes[offset..offset + 4];
+            let is_last = header[0] & 0x80 != 0;
+            headers.push((header[0] & 0x7F, is_last));
+            offset += 4 + u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+            if is_last {
+                return headers;
+            }
+        }
+    }
+
+    fn comment() -> VorbisComment {
+        let mut comment = VorbisComment::default();
+        let () = comment.set("TITLE", "One More Time");
+        let () = comment.set("INITIALKEY", "Am");
+        comment
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = flac_bytes();
+        let flac = FlacFile::parse(bytes.clone()).unwrap();
+        assert_eq!(flac.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn set_vorbis_comment() {
+        let mut flac = FlacFile::parse(flac_bytes()).unwrap();
+        let comment = comment();
+        let () = flac.set_vorbis_comment(&comment).unwrap();
+        let bytes = flac.to_bytes();
+        // The comment follows the streaminfo, only the padding is flagged as the last block
+        assert_eq!(
+            block_headers(&bytes),
+            vec![
+                (STREAMINFO_BLOCK, false),
+                (VORBIS_COMMENT_BLOCK, false),
+                (PADDING_BLOCK, true)
+            ]
+        );
+        let parsed = FlacFile::parse(bytes.clone()).unwrap();
+        assert_eq!(
+            parsed.vorbis_comment().unwrap().to_bytes(),
+            comment.to_bytes()
+        );
+        // The padding absorbed the comment
+        assert_eq!(
+            parsed.blocks[2].data.len(),
+            PADDING_LEN - comment.to_bytes().len()
+        );
+        assert!(bytes.ends_with(FRAMES));
+    }
+
+    #[test]
+    fn comment_larger_than_the_padding() {
+        let mut flac = FlacFile::parse(flac_bytes()).unwrap();
+        let mut comment = comment();
+        let () = comment.set("COMMENT", &"x".repeat(PADDING_LEN * 2));
+        let () = flac.set_vorbis_comment(&comment).unwrap();
+        let parsed = FlacFile::parse(flac.to_bytes()).unwrap();
+        assert_eq!(
+            parsed.vorbis_comment().unwrap().to_bytes(),
+            comment.to_bytes()
+        );
+        assert_eq!(parsed.blocks[2].data.len(), PADDING_LEN);
+        assert_eq!(parsed.frames, FRAMES);
+    }
+
+    #[test]
+    fn set_comment_in_file() {
+        let directory = scratch_dir("flac_set_comment");
+        let path = directory.join("song.flac");
+        let () = std::fs::write(&path, flac_bytes()).unwrap();
+        let () = set_comment(&path, "TITLE", "One More Time").unwrap();
+        let () = set_comment(&path, "INITIALKEY", "F#").unwrap();
+        let () = set_comment(&path, "initialkey", "Am").unwrap();
+        let flac = FlacFile::from_path(&path).unwrap();
+        assert_eq!(
+            flac.vorbis_comment().unwrap().to_bytes(),
+            comment().to_bytes()
+        );
+        assert_eq!(flac.frames, FRAMES);
+    }
+}
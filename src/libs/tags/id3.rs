@@ -0,0 +1,355 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::path::Path;
+
+use super::{write_atomic, TagError, TagResult};
+
+const ID3_HEADER_LEN: usize = 10;
+const FLAG_UNSYNCHRONISATION: u8 = 0x80;
+const FLAG_EXTENDED_HEADER: u8 = 0x40;
+const FLAG_FOOTER: u8 = 0x10;
+const DEFAULT_PADDING: usize = 1024;
+const FRONT_COVER: u8 = 3;
+
+fn syncsafe_to_u32(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |acc, byte| (acc << 7) | (*byte as u32 & 0x7F))
+}
+
+fn u32_to_syncsafe(n: u32) -> [u8; 4] {
+    [
+        ((n >> 21) & 0x7F) as u8,
+        ((n >> 14) & 0x7F) as u8,
+        ((n >> 7) & 0x7F) as u8,
+        (n & 0x7F) as u8,
+    ]
+}
+
+#[derive(Debug, Clone)]
+pub struct Id3Frame {
+    pub id: [u8; 4],
+    pub flags: [u8; 2],
+    pub data: Vec<u8>,
+}
+
+/// ID3v2.3 or ID3v2.4 tag
+#[derive(Debug, Clone)]
+pub struct Id3Tag {
+    major: u8,
+    frames: Vec<Id3Frame>,
+}
+
+impl Default for Id3Tag {
+    fn default() -> Self {
+        Self {
+            major: 3,
+            frames: vec![],
+        }
+    }
+}
+
+impl Id3Tag {
+    /// Parse the tag at the start of bytes and returns it with its size in bytes
+    ///
+    /// Returns [None] if bytes doesn't start with an ID3v2 tag
+    pub fn parse(bytes: &[u8]) -> TagResult<Option<(Self, usize)>> {
+        if bytes.len() < ID3_HEADER_LEN || !bytes.starts_with(b"ID3") {
+            return Ok(None);
+        }
+        let major = bytes[3];
+        let flags = bytes[5];
+        let size = syncsafe_to_u32(&bytes[6..10]) as usize;
+        if major != 3 && major != 4 {
+            return Err(TagError::Unsupported(
+                "only ID3v2.3 and ID3v2.4 are supported",
+            ));
+        }
+        if flags & FLAG_UNSYNCHRONISATION != 0 {
+            return Err(TagError::Unsupported("unsynchronised ID3 tag"));
+        }
+        let tag_end = ID3_HEADER_LEN + size;
+        let body = bytes
+            .get(ID3_HEADER_LEN..tag_end)
+            .ok_or(TagError::Malformed("truncated ID3 tag"))?;
+        let mut offset = 0;
+        if flags & FLAG_EXTENDED_HEADER != 0 {
+            let header = body
+                .get(0..4)
+                .ok_or(TagError::Malformed("truncated ID3 extended header"))?;
+            offset = match major {
+                3 => 4 + u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize,
+                _ => syncsafe_to_u32(header) as usize,
+            };
+        }
+        let mut frames = vec![];
+        while offset + ID3_HEADER_LEN <= body.len() && body[offset] != 0 {
+            let header = &body[offset..offset + ID3_HEADER_LEN];
+            let frame_size = match major {
+                3 => u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+                _ => syncsafe_to_u32(&header[4..8]),
+            } as usize;
+            let data = body
+                .get(offset + ID3_HEADER_LEN..offset + ID3_HEADER_LEN + frame_size)
+                .ok_or(TagError::Malformed("truncated ID3 frame"))?;
+            frames.push(Id3Frame {
+                id: [header[0], header[1], header[2], header[3]],
+                flags: [header[8], header[9]],
+                data: data.to_vec(),
+            });
+            offset += ID3_HEADER_LEN + frame_size;
+        }
+        let footer = if flags & FLAG_FOOTER != 0 {
+            ID3_HEADER_LEN
+        } else {
+            0
+        };
+        Ok(Some((Self { major, frames }, tag_end + footer)))
+    }
+
+    pub fn remove_frame(&mut self, id: &str) {
+        self.frames.retain(|frame| frame.id != id.as_bytes())
+    }
+
+    /// Replace all the frames with the given id
+    pub fn set_frame(&mut self, id: &str, data: Vec<u8>) {
+        let () = self.remove_frame(id);
+        self.add_frame(id, data)
+    }
+
+    pub fn add_frame(&mut self, id: &str, data: Vec<u8>) {
+        let mut frame_id = [b' '; 4];
+        id.bytes()
+            .take(4)
+            .enumerate()
+            .for_each(|(index, byte)| frame_id[index] = byte);
+        self.frames.push(Id3Frame {
+            id: frame_id,
+            flags: [0, 0],
+            data,
+        })
+    }
+
+    /// Set a text frame such as TIT2 or TKEY
+    pub fn set_text(&mut self, id: &str, value: &str) {
+        let data = self.encode_text(value);
+        self.set_frame(id, data)
+    }
+
+    /// Set the recording year, TYER for v2.3 and TDRC for v2.4
+    pub fn set_year(&mut self, year: i16) {
+        let id = match self.major {
+            3 => "TYER",
+            _ => "TDRC",
+        };
+        self.set_text(id, &year.to_string())
+    }
+
+    /// Add an APIC frame with the image file as front cover
+    pub fn add_picture<P: AsRef<Path>>(&mut self, path: P) -> TagResult<()> {
+        let image = std::fs::read(path)?;
+        let format = image::guess_format(&image)
+            .map_err(|_| TagError::Unsupported("unknown image format"))?;
+        let mime = match format {
+            image::ImageFormat::Png => "image/png",
+            image::ImageFormat::Jpeg => "image/jpeg",
+            image::ImageFormat::Gif => "image/gif",
+            image::ImageFormat::Bmp => "image/bmp",
+            image::ImageFormat::WebP => "image/webp",
+            _ => return Err(TagError::Unsupported("unknown image format")),
+        };
+        // ISO-8859-1 mime type, picture type and empty description
+        let mut data = vec![0];
+        data.extend_from_slice(mime.as_bytes());
+        data.extend_from_slice(&[0, FRONT_COVER, 0]);
+        data.extend_from_slice(&image);
+        let () = self.add_frame("APIC", data);
+        Ok(())
+    }
+
+    /// Text with its encoding byte, ISO-8859-1 when possible
+    /// UTF-16 for v2.3 and UTF-8 for v2.4 otherwise
+    pub fn encode_text(&self, value: &str) -> Vec<u8> {
+        if value.is_ascii() {
+            let mut data = vec![0];
+            data.extend_from_slice(value.as_bytes());
+            return data;
+        }
+        match self.major {
+            3 => {
+                let mut data = vec![1, 0xFF, 0xFE];
+                value
+                    .encode_utf16()
+                    .for_each(|unit| data.extend_from_slice(&unit.to_le_bytes()));
+                data
+            }
+            _ => {
+                let mut data = vec![3];
+                data.extend_from_slice(value.as_bytes());
+                data
+            }
+        }
+    }
+
+    /// Serialize the tag without extended header nor footer
+    pub fn to_bytes(&self, padding: usize) -> Vec<u8> {
+        let mut body = vec![];
+        for frame in self.frames.iter() {
+            let size = frame.data.len() as u32;
+            body.extend_from_slice(&frame.id);
+            match self.major {
+                3 => body.extend_from_slice(&size.to_be_bytes()),
+                _ => body.extend_from_slice(&u32_to_syncsafe(size)),
+            };
+            body.extend_from_slice(&frame.flags);
+            body.extend_from_slice(&frame.data);
+        }
+        body.resize(body.len() + padding, 0);
+        let mut bytes = vec![b'I', b'D', b'3', self.major, 0, 0];
+        bytes.extend_from_slice(&u32_to_syncsafe(body.len() as u32));
+        bytes.extend(body);
+        bytes
+    }
+}
+
+/// Set a text frame of the ID3 tag at the start of the file, the tag is created if missing
+pub fn set_text_frame<P: AsRef<Path>>(path: P, id: &str, value: &str) -> TagResult<()> {
+    let bytes = std::fs::read(&path)?;
+    let (mut tag, tag_len) = Id3Tag::parse(&bytes)?.unwrap_or_default();
+    let () = tag.set_text(id, value);
+    let mut content = tag.to_bytes(DEFAULT_PADDING);
+    content.extend_from_slice(&bytes[tag_len..]);
+    write_atomic(path, &content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_text_frame, syncsafe_to_u32, u32_to_syncsafe, Id3Frame, Id3Tag};
+    use crate::libs::tags::tests::scratch_dir;
+
+    const AUDIO: &[u8] = b"\xFF\xFBaudio frames";
+
+    fn frame<'a>(tag: &'a Id3Tag, id: &[u8; 4]) -> Option<&'a [u8]> {
+        tag.frames
+            .iter()
+            .find(|frame| &frame.id == id)
+            .map(|frame| frame.data.as_slice())
+    }
+
+    #[test]
+    fn syncsafe() {
+        assert_eq!(u32_to_syncsafe(257), [0, 0, 2, 1]);
+        assert_eq!(syncsafe_to_u32(&[0, 0, 2, 1]), 257);
+        assert_eq!(syncsafe_to_u32(&[0x7F; 4]), 0x0FFF_FFFF);
+        for n in [0, 127, 128, 1024, 0x0FFF_FFFF] {
+            assert_eq!(syncsafe_to_u32(&u32_to_syncsafe(n)), n)
+        }
+    }
+
+    #[test]
+    fn round_trip_v23() {
+        let mut tag = Id3Tag::default();
+        let () = tag.set_text("TIT2", "One More Time");
+        let () = tag.set_text("TALB", "Homéwork");
+        let () = tag.set_year(1997);
+        let bytes = tag.to_bytes(100);
+        let (parsed, len) = Id3Tag::parse(&bytes).unwrap().unwrap();
+        // The padding is part of the tag
+        assert_eq!(len, bytes.len());
+        assert_eq!(parsed.major, 3);
+        assert_eq!(parsed.frames.len(), 3);
+        assert_eq!(frame(&parsed, b"TIT2"), Some(&b"\x00One More Time"[..]));
+        assert_eq!(frame(&parsed, b"TYER"), Some(&b"\x001997"[..]));
+        // Utf-16 with a bom for the non ISO-8859-1 texts
+        let album = frame(&parsed, b"TALB").unwrap();
+        assert_eq!(&album[..3], &[1, 0xFF, 0xFE]);
+        let units = album[3..]
+            .chunks(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect::<Vec<_>>();
+        assert_eq!(String::from_utf16(&units).unwrap(), "Homéwork");
+    }
+
+    #[test]
+    fn round_trip_v24_syncsafe_frames() {
+        // A frame larger than 127 bytes has a different syncsafe and plain size
+        let data = vec![b'x'; 300];
+        let tag = Id3Tag {
+            major: 4,
+            frames: vec![Id3Frame {
+                id: *b"COMM",
+                flags: [0, 0],
+                data: data.clone(),
+            }],
+        };
+        let bytes = tag.to_bytes(0);
+        assert_eq!(&bytes[10 + 4..10 + 8], &u32_to_syncsafe(300));
+        let (mut parsed, len) = Id3Tag::parse(&bytes).unwrap().unwrap();
+        assert_eq!(len, bytes.len());
+        assert_eq!(frame(&parsed, b"COMM"), Some(data.as_slice()));
+        // Utf-8 for the non ISO-8859-1 texts
+        let () = parsed.set_text("TPE1", "Daft Pünk");
+        assert_eq!(frame(&parsed, b"TPE1").unwrap()[0], 3);
+    }
+
+    #[test]
+    fn set_text_replaces_the_frame() {
+        let mut tag = Id3Tag::default();
+        let () = tag.set_text("TKEY", "Am");
+        let () = tag.set_text("TKEY", "F#");
+        let (parsed, _) = Id3Tag::parse(&tag.to_bytes(0)).unwrap().unwrap();
+        assert_eq!(parsed.frames.len(), 1);
+        assert_eq!(frame(&parsed, b"TKEY"), Some(&b"\x00F#"[..]));
+    }
+
+    #[test]
+    fn not_an_id3_tag() {
+        assert!(Id3Tag::parse(AUDIO).unwrap().is_none());
+        assert!(Id3Tag::parse(b"ID3\x02\x00\x00\x00\x00\x00\x00").is_err());
+    }
+
+    #[test]
+    fn set_text_frame_keeps_the_audio() {
+        let directory = scratch_dir("id3_set_text_frame");
+        let path = directory.join("song.mp3");
+        let mut tag = Id3Tag::default();
+        let () = tag.set_text("TIT2", "Aerodynamic");
+        let mut content = tag.to_bytes(4);
+        content.extend_from_slice(AUDIO);
+        let () = std::fs::write(&path, content).unwrap();
+
+        let () = set_text_frame(&path, "TKEY", "Am").unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let (parsed, len) = Id3Tag::parse(&bytes).unwrap().unwrap();
+        assert_eq!(frame(&parsed, b"TIT2"), Some(&b"\x00Aerodynamic"[..]));
+        assert_eq!(frame(&parsed, b"TKEY"), Some(&b"\x00Am"[..]));
+        assert_eq!(&bytes[len..], AUDIO);
+    }
+
+    #[test]
+    fn set_text_frame_creates_the_tag() {
+        let directory = scratch_dir("id3_create_tag");
+        let path = directory.join("song.mp3");
+        let () = std::fs::write(&path, AUDIO).unwrap();
+        let () = set_text_frame(&path, "TBPM", "123").unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let (parsed, len) = Id3Tag::parse(&bytes).unwrap().unwrap();
+        assert_eq!(frame(&parsed, b"TBPM"), Some(&b"\x00123"[..]));
+        assert_eq!(&bytes[len..], AUDIO);
+    }
+}
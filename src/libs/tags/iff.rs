@@ -0,0 +1,419 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::path::Path;
+
+use super::{id3::Id3Tag, write_atomic, TagError, TagResult};
+
+const CHUNK_HEADER_LEN: usize = 8;
+const INFO_LIST: &[u8; 4] = b"INFO";
+
+/// Byte order of the chunk sizes, little endian for RIFF and big endian for AIFF
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    fn read(&self, bytes: &[u8]) -> usize {
+        let size = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            Endian::Little => u32::from_le_bytes(size) as usize,
+            Endian::Big => u32::from_be_bytes(size) as usize,
+        }
+    }
+
+    fn write(&self, size: usize) -> TagResult<[u8; 4]> {
+        let size =
+            u32::try_from(size).map_err(|_| TagError::Unsupported("chunk larger than 4GB"))?;
+        match self {
+            Endian::Little => Ok(size.to_le_bytes()),
+            Endian::Big => Ok(size.to_be_bytes()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Chunk {
+    id: [u8; 4],
+    data: Vec<u8>,
+}
+
+/// Iterate over the chunks of bytes, the pad byte of the odd sized chunks is skipped.
+/// Also returns the trailing bytes too short to be a chunk
+fn parse_chunks(mut bytes: &[u8], endian: Endian) -> TagResult<(Vec<Chunk>, Vec<u8>)> {
+    let mut chunks = vec![];
+    while bytes.len() >= CHUNK_HEADER_LEN {
+        let mut id = [0; 4];
+        id.copy_from_slice(&bytes[..4]);
+        let size = endian.read(&bytes[4..8]);
+        let data = bytes
+            .get(CHUNK_HEADER_LEN..CHUNK_HEADER_LEN + size)
+            .ok_or(TagError::Malformed("truncated chunk"))?;
+        chunks.push(Chunk {
+            id,
+            data: data.to_vec(),
+        });
+        // The pad byte of the last chunk is sometimes missing
+        let len = (CHUNK_HEADER_LEN + size + size % 2).min(bytes.len());
+        bytes = &bytes[len..];
+    }
+    Ok((chunks, bytes.to_vec()))
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], data: &[u8], endian: Endian) -> TagResult<()> {
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(&endian.write(data.len())?);
+    bytes.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        bytes.push(0)
+    }
+    Ok(())
+}
+
+/// Wav (RIFF) or Aiff (FORM) file split between its chunks
+pub struct IffFile {
+    endian: Endian,
+    form_type: [u8; 4],
+    chunks: Vec<Chunk>,
+    /// Bytes at the end of the form too short to be a chunk
+    fragment: Vec<u8>,
+    /// Bytes following the form, past its declared size
+    trailing: Vec<u8>,
+}
+
+impl IffFile {
+    pub fn parse(bytes: &[u8]) -> TagResult<Self> {
+        let endian = match bytes.get(..4) {
+            Some(b"RIFF") => Endian::Little,
+            Some(b"FORM") => Endian::Big,
+            Some(b"RF64") => return Err(TagError::Unsupported("RF64 wav file")),
+            _ => return Err(TagError::Malformed("neither a RIFF nor a FORM file")),
+        };
+        let header = bytes
+            .get(..CHUNK_HEADER_LEN + 4)
+            .ok_or(TagError::Malformed("truncated file header"))?;
+        let mut form_type = [0; 4];
+        form_type.copy_from_slice(&header[8..12]);
+        let size = endian.read(&header[4..8]);
+        if size < 4 {
+            return Err(TagError::Malformed("form size smaller than its type"));
+        }
+        let end = (CHUNK_HEADER_LEN + size).min(bytes.len());
+        let (chunks, fragment) = parse_chunks(&bytes[CHUNK_HEADER_LEN + 4..end], endian)?;
+        // The pad byte of an odd sized form is written back with it
+        let trailing = bytes
+            .get(end + size % 2..)
+            .map(<[u8]>::to_vec)
+            .unwrap_or_default();
+        Ok(Self {
+            endian,
+            form_type,
+            chunks,
+            fragment,
+            trailing,
+        })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> TagResult<Self> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    pub fn is_wav(&self) -> bool {
+        self.endian == Endian::Little
+    }
+
+    fn chunk_mut(&mut self, id: &[u8; 4]) -> Option<&mut Chunk> {
+        self.chunks.iter_mut().find(|chunk| &chunk.id == id)
+    }
+
+    /// Replace the data of the first chunk with this id, or append a new chunk
+    fn set_chunk(&mut self, id: &[u8; 4], data: Vec<u8>) {
+        match self.chunk_mut(id) {
+            Some(chunk) => chunk.data = data,
+            None => self.chunks.push(Chunk { id: *id, data }),
+        }
+    }
+
+    fn id3_chunk_index(&self) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|chunk| chunk.id.eq_ignore_ascii_case(b"id3 "))
+    }
+
+    /// The embedded ID3 tag, an empty one if missing
+    pub fn id3_tag(&self) -> TagResult<Id3Tag> {
+        let tag = match self.id3_chunk_index() {
+            Some(index) => Id3Tag::parse(&self.chunks[index].data)?.map(|(tag, _)| tag),
+            None => None,
+        };
+        Ok(tag.unwrap_or_default())
+    }
+
+    /// Write the ID3 tag in the `id3 ` chunk of a wav or the `ID3 ` chunk of an aiff
+    pub fn set_id3_tag(&mut self, tag: &Id3Tag) {
+        let data = tag.to_bytes(0);
+        match self.id3_chunk_index() {
+            Some(index) => self.chunks[index].data = data,
+            None => {
+                let id = if self.is_wav() { b"id3 " } else { b"ID3 " };
+                self.chunks.push(Chunk { id: *id, data })
+            }
+        }
+    }
+
+    /// Set a text field of the wav `LIST/INFO` chunk such as INAM or IART
+    pub fn set_info(&mut self, id: &[u8; 4], value: &str) -> TagResult<()> {
+        if !self.is_wav() {
+            return Err(TagError::Unsupported("INFO list outside of a wav file"));
+        }
+        let index = self
+            .chunks
+            .iter()
+            .position(|chunk| &chunk.id == b"LIST" && chunk.data.starts_with(INFO_LIST));
+        let (mut fields, fragment) = match index {
+            Some(index) => parse_chunks(&self.chunks[index].data[4..], Endian::Little)?,
+            None => (vec![], vec![]),
+        };
+        // Null terminated string
+        let mut text = value.as_bytes().to_vec();
+        text.push(0);
+        match fields.iter_mut().find(|field| &field.id == id) {
+            Some(field) => field.data = text,
+            None => fields.push(Chunk {
+                id: *id,
+                data: text,
+            }),
+        }
+        let mut data = INFO_LIST.to_vec();
+        for field in fields.iter() {
+            write_chunk(&mut data, &field.id, &field.data, Endian::Little)?
+        }
+        data.extend_from_slice(&fragment);
+        match index {
+            Some(index) => self.chunks[index].data = data,
+            None => self.chunks.push(Chunk { id: *b"LIST", data }),
+        }
+        Ok(())
+    }
+
+    /// Set an aiff text chunk such as NAME or AUTH
+    pub fn set_text_chunk(&mut self, id: &[u8; 4], value: &str) -> TagResult<()> {
+        if self.is_wav() {
+            return Err(TagError::Unsupported("aiff text chunk in a wav file"));
+        }
+        let () = self.set_chunk(id, value.as_bytes().to_vec());
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> TagResult<Vec<u8>> {
+        let mut body = self.form_type.to_vec();
+        for chunk in self.chunks.iter() {
+            write_chunk(&mut body, &chunk.id, &chunk.data, self.endian)?
+        }
+        body.extend_from_slice(&self.fragment);
+        let mut bytes = vec![];
+        let id = if self.is_wav() { b"RIFF" } else { b"FORM" };
+        let () = write_chunk(&mut bytes, id, &body, self.endian)?;
+        bytes.extend_from_slice(&self.trailing);
+        Ok(bytes)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> TagResult<()> {
+        write_atomic(path, &self.to_bytes()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_chunks, write_chunk, Chunk, Endian, IffFile};
+    use crate::libs::tags::{id3::Id3Tag, tests::scratch_dir, TagError};
+
+    const FMT: &[u8] = b"\x01\x00\x02\x00\x44\xAC\x00\x00\x10\xB1\x02\x00\x04\x00\x10\x00";
+
+    fn form(endian: Endian, id: &[u8; 4], form_type: &[u8; 4], chunks: &[Chunk]) -> Vec<u8> {
+        let mut body = form_type.to_vec();
+        for chunk in chunks {
+            let () = write_chunk(&mut body, &chunk.id, &chunk.data, endian).unwrap();
+        }
+        let mut bytes = vec![];
+        let () = write_chunk(&mut bytes, id, &body, endian).unwrap();
+        bytes
+    }
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Chunk {
+        Chunk {
+            id: *id,
+            data: data.to_vec(),
+        }
+    }
+
+    fn info(fields: &[(&[u8; 4], &[u8])]) -> Chunk {
+        let mut data = b"INFO".to_vec();
+        for (id, value) in fields {
+            let () = write_chunk(&mut data, id, value, Endian::Little).unwrap();
+        }
+        chunk(b"LIST", &data)
+    }
+
+    /// A wav with an odd sized data chunk followed by an INFO list
+    fn wav() -> Vec<u8> {
+        form(
+            Endian::Little,
+            b"RIFF",
+            b"WAVE",
+            &[
+                chunk(b"fmt ", FMT),
+                chunk(b"data", &[1, 2, 3]),
+                info(&[(b"INAM", b"Da Funk\0"), (b"ISFT", b"Lavf\0")]),
+            ],
+        )
+    }
+
+    fn ids(iff: &IffFile) -> Vec<&[u8; 4]> {
+        iff.chunks.iter().map(|chunk| &chunk.id).collect()
+    }
+
+    #[test]
+    fn pad_bytes() {
+        let bytes = wav();
+        // The pad byte follows the 3 bytes of data
+        let data = 12 + 8 + FMT.len();
+        assert_eq!(
+            &bytes[data..data + 12],
+            b"data\x03\x00\x00\x00\x01\x02\x03\x00"
+        );
+        let iff = IffFile::parse(&bytes).unwrap();
+        assert_eq!(ids(&iff), vec![b"fmt ", b"data", b"LIST"]);
+        assert_eq!(iff.chunks[1].data, vec![1, 2, 3]);
+        assert_eq!(iff.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn missing_last_pad_byte() {
+        let mut bytes = form(
+            Endian::Little,
+            b"RIFF",
+            b"WAVE",
+            &[chunk(b"data", &[1, 2, 3])],
+        );
+        bytes.pop();
+        bytes[4] -= 1;
+        let iff = IffFile::parse(&bytes).unwrap();
+        assert_eq!(iff.chunks[0].data, vec![1, 2, 3]);
+        assert!(iff.fragment.is_empty() && iff.trailing.is_empty());
+    }
+
+    #[test]
+    fn info_list() {
+        let mut iff = IffFile::parse(&wav()).unwrap();
+        let () = iff.set_info(b"INAM", "Around the World").unwrap();
+        let () = iff.set_info(b"IART", "Daft Punk").unwrap();
+        let iff = IffFile::parse(&iff.to_bytes().unwrap()).unwrap();
+        assert_eq!(ids(&iff), vec![b"fmt ", b"data", b"LIST"]);
+        let list = &iff.chunks[2].data;
+        assert!(list.starts_with(b"INFO"));
+        let (fields, fragment) = parse_chunks(&list[4..], Endian::Little).unwrap();
+        assert!(fragment.is_empty());
+        let fields = fields
+            .iter()
+            .map(|field| (&field.id, field.data.as_slice()))
+            .collect::<Vec<_>>();
+        // The odd sized values are padded in the list
+        assert_eq!(
+            fields,
+            vec![
+                (b"INAM", &b"Around the World\0"[..]),
+                (b"ISFT", &b"Lavf\0"[..]),
+                (b"IART", &b"Daft Punk\0"[..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn info_list_created() {
+        let bytes = form(Endian::Little, b"RIFF", b"WAVE", &[chunk(b"fmt ", FMT)]);
+        let mut iff = IffFile::parse(&bytes).unwrap();
+        let () = iff.set_info(b"INAM", "Revolution 909").unwrap();
+        let expected = form(
+            Endian::Little,
+            b"RIFF",
+            b"WAVE",
+            &[chunk(b"fmt ", FMT), info(&[(b"INAM", b"Revolution 909\0")])],
+        );
+        assert_eq!(iff.to_bytes().unwrap(), expected);
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let mut bytes = wav();
+        let size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        // A fragment inside the form and an odd sized junk after it
+        bytes.extend_from_slice(b"frag");
+        bytes[4..8].copy_from_slice(&(size + 4).to_le_bytes());
+        bytes.extend_from_slice(b"junk!");
+        let mut iff = IffFile::parse(&bytes).unwrap();
+        assert_eq!(iff.fragment, b"frag");
+        assert_eq!(iff.trailing, b"junk!");
+        assert_eq!(iff.to_bytes().unwrap(), bytes);
+        let () = iff.set_info(b"IART", "Daft Punk").unwrap();
+        assert!(iff.to_bytes().unwrap().ends_with(b"fragjunk!"));
+    }
+
+    #[test]
+    fn aiff() {
+        let bytes = form(
+            Endian::Big,
+            b"FORM",
+            b"AIFF",
+            &[chunk(b"COMM", &[0; 18]), chunk(b"SSND", &[0; 9])],
+        );
+        assert_eq!(&bytes[4..8], &(bytes.len() as u32 - 8).to_be_bytes());
+        let mut iff = IffFile::parse(&bytes).unwrap();
+        assert!(!iff.is_wav());
+        assert!(iff.set_info(b"INAM", "Da Funk").is_err());
+        let () = iff.set_text_chunk(b"NAME", "Da Funk").unwrap();
+        let mut tag = Id3Tag::default();
+        let () = tag.set_text("TKEY", "Am");
+        let () = iff.set_id3_tag(&tag);
+        let iff = IffFile::parse(&iff.to_bytes().unwrap()).unwrap();
+        assert_eq!(ids(&iff), vec![b"COMM", b"SSND", b"NAME", b"ID3 "]);
+        assert_eq!(iff.chunks[2].data, b"Da Funk");
+        assert_eq!(iff.id3_tag().unwrap().to_bytes(0), tag.to_bytes(0));
+    }
+
+    #[test]
+    fn chunk_size_overflow() {
+        assert!(matches!(
+            Endian::Little.write(u32::MAX as usize + 1),
+            Err(TagError::Unsupported(_))
+        ));
+        assert_eq!(Endian::Big.write(u32::MAX as usize).unwrap(), [0xFF; 4]);
+    }
+
+    #[test]
+    fn write() {
+        let directory = scratch_dir("iff_write");
+        let path = directory.join("song.wav");
+        let () = std::fs::write(&path, wav()).unwrap();
+        let mut iff = IffFile::from_path(&path).unwrap();
+        let () = iff.set_info(b"INAM", "Burnin'").unwrap();
+        let () = iff.write(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), iff.to_bytes().unwrap());
+    }
+}
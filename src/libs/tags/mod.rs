@@ -0,0 +1,136 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+pub mod container;
+pub mod flac;
+pub mod id3;
+pub mod iff;
+pub mod mp4;
+pub mod ogg;
+pub mod vorbis;
+
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+#[derive(Debug)]
+pub enum TagError {
+    Io(std::io::Error),
+    /// The file doesn't have the expected layout
+    Malformed(&'static str),
+    Unsupported(&'static str),
+}
+
+impl Display for TagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagError::Io(e) => write!(f, "{}", e),
+            TagError::Malformed(s) => write!(f, "Malformed file: {}", s),
+            TagError::Unsupported(s) => write!(f, "Unsupported file: {}", s),
+        }
+    }
+}
+
+impl From<std::io::Error> for TagError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+pub type TagResult<T> = Result<T, TagError>;
+
+/// Replace the content of the file without ever leaving it half written.
+///
+/// The bytes go to a temporary file of the same directory which is synced
+/// and then renamed over the original one
+pub fn write_atomic<P: AsRef<Path>>(path: P, bytes: &[u8]) -> TagResult<()> {
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .ok_or(TagError::Unsupported("not a file path"))?;
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let temporary = directory.join(format!(
+        ".{}.koto-{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    // Left over by an interrupted run
+    let _ = std::fs::remove_file(&temporary);
+    if let Err(e) = write_synced(&temporary, path, bytes) {
+        let _ = std::fs::remove_file(&temporary);
+        return Err(e.into());
+    }
+    // Persist the rename, not every platform can open a directory
+    if let Ok(directory) = File::open(directory) {
+        let _ = directory.sync_all();
+    }
+    Ok(())
+}
+
+/// Write the bytes in a new file with the permissions of original and flush it to the disk
+fn write_synced(path: &Path, original: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    let () = file.write_all(bytes)?;
+    if let Ok(metadata) = std::fs::metadata(original) {
+        let () = file.set_permissions(metadata.permissions())?;
+    }
+    let () = file.sync_all()?;
+    std::fs::rename(path, original)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::path::PathBuf;
+
+    use super::write_atomic;
+
+    /// Empty directory for the files of a test
+    pub(crate) fn scratch_dir(test: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir()
+                .join("koto-tags")
+                .join(format!("{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let () = std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn write_atomic_replaces_the_file() {
+        let directory = scratch_dir("write_atomic");
+        let path = directory.join("song.flac");
+        let () = std::fs::write(&path, b"old content, longer than the new one").unwrap();
+        let () = write_atomic(&path, b"new content").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new content");
+        // Only the file itself is left, without any temporary file
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn write_atomic_creates_the_file() {
+        let directory = scratch_dir("write_atomic_create");
+        let path = directory.join("output.mp3");
+        let () = write_atomic(&path, b"content").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"content");
+    }
+}
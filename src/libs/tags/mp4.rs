@@ -0,0 +1,587 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::path::Path;
+
+use super::{write_atomic, TagError, TagResult};
+
+const ATOM_HEADER_LEN: usize = 8;
+const LARGE_ATOM_HEADER_LEN: usize = 16;
+/// Atoms whose content is only made of atoms
+const CONTAINERS: [&[u8; 4]; 8] = [
+    b"moov", b"trak", b"mdia", b"minf", b"stbl", b"udta", b"meta", b"ilst",
+];
+/// Well known type of the `data` atoms
+const DATA_IMPLICIT: u32 = 0;
+const DATA_UTF8: u32 = 1;
+const DATA_JPEG: u32 = 13;
+const DATA_PNG: u32 = 14;
+const DATA_SIGNED_INT: u32 = 21;
+const DATA_BMP: u32 = 27;
+/// Namespace of the freeform atoms written by itunes
+const ITUNES_MEAN: &str = "com.apple.iTunes";
+
+#[derive(Debug, Clone)]
+enum AtomBody {
+    Leaf(Vec<u8>),
+    /// `prefix` holds the version and flags of a full box container such as `meta`
+    Container {
+        prefix: Vec<u8>,
+        children: Vec<Atom>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Atom {
+    kind: [u8; 4],
+    body: AtomBody,
+}
+
+/// Read the atom header at the start of `bytes`, returns the atom type, header and total lengths
+fn atom_header(bytes: &[u8]) -> TagResult<([u8; 4], usize, usize)> {
+    let header = bytes
+        .get(..ATOM_HEADER_LEN)
+        .ok_or(TagError::Malformed("truncated mp4 atom"))?;
+    let mut kind = [0; 4];
+    kind.copy_from_slice(&header[4..8]);
+    let (header_len, len) = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+        0 => (ATOM_HEADER_LEN, bytes.len()),
+        1 => {
+            let large = bytes
+                .get(ATOM_HEADER_LEN..LARGE_ATOM_HEADER_LEN)
+                .ok_or(TagError::Malformed("truncated mp4 atom"))?;
+            let mut len = [0; 8];
+            len.copy_from_slice(large);
+            (LARGE_ATOM_HEADER_LEN, u64::from_be_bytes(len) as usize)
+        }
+        len => (ATOM_HEADER_LEN, len as usize),
+    };
+    if len < header_len || len > bytes.len() {
+        return Err(TagError::Malformed("mp4 atom size out of bounds"));
+    }
+    Ok((kind, header_len, len))
+}
+
+impl Atom {
+    fn parse_all(mut bytes: &[u8], in_ilst: bool) -> TagResult<Vec<Self>> {
+        let mut atoms = vec![];
+        while !bytes.is_empty() {
+            let (kind, header_len, len) = atom_header(bytes)?;
+            atoms.push(Self::parse(kind, &bytes[header_len..len], in_ilst)?);
+            bytes = &bytes[len..];
+        }
+        Ok(atoms)
+    }
+
+    fn parse(kind: [u8; 4], body: &[u8], in_ilst: bool) -> TagResult<Self> {
+        // The items of ilst hold the data atoms
+        if !in_ilst && !CONTAINERS.contains(&&kind) {
+            return Ok(Self::leaf(kind, body.to_vec()));
+        }
+        // The itunes meta is a full box, unlike the quicktime one which starts with its hdlr
+        let prefix_len = match &kind == b"meta" && body.get(4..8) != Some(b"hdlr") {
+            true => 4,
+            false => 0,
+        };
+        let prefix = body
+            .get(..prefix_len)
+            .ok_or(TagError::Malformed("truncated mp4 meta atom"))?
+            .to_vec();
+        let children = Self::parse_all(&body[prefix_len..], &kind == b"ilst")?;
+        Ok(Self {
+            kind,
+            body: AtomBody::Container { prefix, children },
+        })
+    }
+
+    fn leaf(kind: [u8; 4], data: Vec<u8>) -> Self {
+        Self {
+            kind,
+            body: AtomBody::Leaf(data),
+        }
+    }
+
+    fn container(kind: [u8; 4], prefix: Vec<u8>, children: Vec<Atom>) -> Self {
+        Self {
+            kind,
+            body: AtomBody::Container { prefix, children },
+        }
+    }
+
+    /// `data` atom of an ilst item
+    fn data(data_type: u32, payload: &[u8]) -> Self {
+        let mut data = data_type.to_be_bytes().to_vec();
+        // Locale
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(payload);
+        Self::leaf(*b"data", data)
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<Atom>> {
+        match &mut self.body {
+            AtomBody::Leaf(_) => None,
+            AtomBody::Container { children, .. } => Some(children),
+        }
+    }
+
+    fn child_mut(&mut self, kind: &[u8; 4]) -> Option<&mut Atom> {
+        self.children_mut()?
+            .iter_mut()
+            .find(|atom| &atom.kind == kind)
+    }
+
+    /// The child container, created at the end if missing
+    fn child_or_insert(&mut self, kind: &[u8; 4], prefix: Vec<u8>) -> TagResult<&mut Atom> {
+        let children = self
+            .children_mut()
+            .ok_or(TagError::Malformed("mp4 container expected"))?;
+        let index = match children.iter().position(|atom| &atom.kind == kind) {
+            Some(index) => index,
+            None => {
+                children.push(Self::container(*kind, prefix, vec![]));
+                children.len() - 1
+            }
+        };
+        Ok(&mut children[index])
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut body = vec![];
+        match &self.body {
+            AtomBody::Leaf(data) => body.extend_from_slice(data),
+            AtomBody::Container { prefix, children } => {
+                body.extend_from_slice(prefix);
+                for child in children {
+                    body.extend_from_slice(&child.to_bytes())
+                }
+            }
+        }
+        let mut bytes = Vec::with_capacity(body.len() + LARGE_ATOM_HEADER_LEN);
+        match u32::try_from(body.len() + ATOM_HEADER_LEN) {
+            Ok(len) => {
+                bytes.extend_from_slice(&len.to_be_bytes());
+                bytes.extend_from_slice(&self.kind);
+            }
+            Err(_) => {
+                bytes.extend_from_slice(&1u32.to_be_bytes());
+                bytes.extend_from_slice(&self.kind);
+                bytes.extend_from_slice(
+                    &((body.len() + LARGE_ATOM_HEADER_LEN) as u64).to_be_bytes(),
+                );
+            }
+        }
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    /// Add `delta` to the chunk offsets pointing at or after `from`
+    fn shift_chunk_offsets(&mut self, from: u64, delta: i64) -> TagResult<()> {
+        match &mut self.body {
+            AtomBody::Container { children, .. } => children
+                .iter_mut()
+                .try_for_each(|child| child.shift_chunk_offsets(from, delta)),
+            AtomBody::Leaf(data) if &self.kind == b"stco" || &self.kind == b"co64" => {
+                let width = if &self.kind == b"stco" { 4 } else { 8 };
+                // Version, flags and entry count
+                let entries = data
+                    .get_mut(8..)
+                    .ok_or(TagError::Malformed("truncated chunk offset table"))?;
+                for entry in entries.chunks_exact_mut(width) {
+                    let offset = entry
+                        .iter()
+                        .fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+                    if offset < from {
+                        continue;
+                    }
+                    let offset = (offset as i64 + delta) as u64;
+                    match width {
+                        4 => entry.copy_from_slice(
+                            &u32::try_from(offset)
+                                .map_err(|_| {
+                                    TagError::Unsupported("chunk offset beyond 4GB in stco")
+                                })?
+                                .to_be_bytes(),
+                        ),
+                        _ => entry.copy_from_slice(&offset.to_be_bytes()),
+                    }
+                }
+                Ok(())
+            }
+            AtomBody::Leaf(_) => Ok(()),
+        }
+    }
+}
+
+/// Mp4 file with its `moov` atom parsed
+pub struct Mp4File {
+    bytes: Vec<u8>,
+    moov_offset: usize,
+    moov_len: usize,
+    moov: Atom,
+}
+
+impl Mp4File {
+    pub fn parse(bytes: Vec<u8>) -> TagResult<Self> {
+        let mut offset = 0;
+        let mut moov = None;
+        while offset < bytes.len() {
+            let (kind, header_len, len) = atom_header(&bytes[offset..])?;
+            match &kind {
+                b"moof" => return Err(TagError::Unsupported("fragmented mp4")),
+                b"moov" => {
+                    let body = &bytes[offset + header_len..offset + len];
+                    moov = Some((offset, len, Atom::parse(kind, body, false)?))
+                }
+                _ => (),
+            }
+            offset += len;
+        }
+        let (moov_offset, moov_len, moov) = moov.ok_or(TagError::Malformed("missing moov atom"))?;
+        Ok(Self {
+            bytes,
+            moov_offset,
+            moov_len,
+            moov,
+        })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> TagResult<Self> {
+        Self::parse(std::fs::read(path)?)
+    }
+
+    /// Items of `moov/udta/meta/ilst`, the atoms are created if missing
+    fn ilst_mut(&mut self) -> TagResult<&mut Vec<Atom>> {
+        let meta = self
+            .moov
+            .child_or_insert(b"udta", vec![])?
+            .child_or_insert(b"meta", vec![0; 4])?;
+        if meta.child_mut(b"hdlr").is_none() {
+            let mut hdlr = vec![0; 8];
+            hdlr.extend_from_slice(b"mdir");
+            hdlr.extend_from_slice(b"appl");
+            hdlr.extend_from_slice(&[0; 9]);
+            let children = meta.children_mut().ok_or(TagError::Malformed("meta"))?;
+            children.insert(0, Atom::leaf(*b"hdlr", hdlr));
+        }
+        meta.child_or_insert(b"ilst", vec![])?
+            .children_mut()
+            .ok_or(TagError::Malformed("ilst atom expected"))
+    }
+
+    fn set_item(&mut self, kind: &[u8; 4], data: Vec<Atom>) -> TagResult<()> {
+        let ilst = self.ilst_mut()?;
+        let item = Atom::container(*kind, vec![], data);
+        match ilst.iter_mut().find(|atom| &atom.kind == kind) {
+            Some(atom) => *atom = item,
+            None => ilst.push(item),
+        }
+        Ok(())
+    }
+
+    /// Set a text item such as `©nam`, `©ART`, `aART`, `©alb` or `©day`
+    pub fn set_text(&mut self, kind: &[u8; 4], value: &str) -> TagResult<()> {
+        self.set_item(kind, vec![Atom::data(DATA_UTF8, value.as_bytes())])
+    }
+
+    /// Total of an existing `trkn` or `disk` item
+    fn total(&mut self, kind: &[u8; 4]) -> TagResult<u16> {
+        let ilst = self.ilst_mut()?;
+        let total = ilst
+            .iter_mut()
+            .find(|atom| &atom.kind == kind)
+            .and_then(|item| item.child_mut(b"data"))
+            .and_then(|data| match &data.body {
+                AtomBody::Leaf(data) => data.get(12..14).map(|t| u16::from_be_bytes([t[0], t[1]])),
+                AtomBody::Container { .. } => None,
+            });
+        Ok(total.unwrap_or(0))
+    }
+
+    /// Set the track position, the existing total is kept
+    pub fn set_track(&mut self, track: u16) -> TagResult<()> {
+        let total = self.total(b"trkn")?;
+        let mut payload = vec![0, 0];
+        payload.extend_from_slice(&track.to_be_bytes());
+        payload.extend_from_slice(&total.to_be_bytes());
+        payload.extend_from_slice(&[0, 0]);
+        self.set_item(b"trkn", vec![Atom::data(DATA_IMPLICIT, &payload)])
+    }
+
+    /// Set the disc position, the existing total is kept
+    pub fn set_disc(&mut self, disc: u16) -> TagResult<()> {
+        let total = self.total(b"disk")?;
+        let mut payload = vec![0, 0];
+        payload.extend_from_slice(&disc.to_be_bytes());
+        payload.extend_from_slice(&total.to_be_bytes());
+        self.set_item(b"disk", vec![Atom::data(DATA_IMPLICIT, &payload)])
+    }
+
+    pub fn set_bpm(&mut self, bpm: u16) -> TagResult<()> {
+        self.set_item(
+            b"tmpo",
+            vec![Atom::data(DATA_SIGNED_INT, &bpm.to_be_bytes())],
+        )
+    }
+
+    /// Set an itunes freeform item (`----:com.apple.iTunes:<name>`)
+    pub fn set_freeform(&mut self, name: &str, value: &str) -> TagResult<()> {
+        let ilst = self.ilst_mut()?;
+        let is_named = |atom: &Atom| {
+            match &atom.body {
+            AtomBody::Container { children, .. } => children.iter().any(|child| {
+                matches!(&child.body, AtomBody::Leaf(data) if &child.kind == b"name" && data.get(4..) == Some(name.as_bytes()))
+            }),
+            AtomBody::Leaf(_) => false,
+        }
+        };
+        ilst.retain(|atom| !(&atom.kind == b"----" && is_named(atom)));
+        let full_box = |text: &str| {
+            let mut data = vec![0; 4];
+            data.extend_from_slice(text.as_bytes());
+            data
+        };
+        ilst.push(Atom::container(
+            *b"----",
+            vec![],
+            vec![
+                Atom::leaf(*b"mean", full_box(ITUNES_MEAN)),
+                Atom::leaf(*b"name", full_box(name)),
+                Atom::data(DATA_UTF8, value.as_bytes()),
+            ],
+        ));
+        Ok(())
+    }
+
+    /// Add a cover to the `covr` item
+    pub fn add_cover<P: AsRef<Path>>(&mut self, path: P) -> TagResult<()> {
+        let image = std::fs::read(path)?;
+        let data_type = match image.as_slice() {
+            [0xFF, 0xD8, ..] => DATA_JPEG,
+            [0x89, b'P', b'N', b'G', ..] => DATA_PNG,
+            [b'B', b'M', ..] => DATA_BMP,
+            _ => return Err(TagError::Unsupported("cover neither jpeg, png nor bmp")),
+        };
+        let ilst = self.ilst_mut()?;
+        let cover = Atom::data(data_type, &image);
+        match ilst.iter_mut().find(|atom| &atom.kind == b"covr") {
+            Some(covr) => covr
+                .children_mut()
+                .ok_or(TagError::Malformed("covr atom"))?
+                .push(cover),
+            None => ilst.push(Atom::container(*b"covr", vec![], vec![cover])),
+        }
+        Ok(())
+    }
+
+    /// Rewrite the file with the new `moov`.
+    ///
+    /// A `free` atom right after `moov` absorbs the size difference when possible,
+    /// otherwise the chunk offsets of the media data following `moov` are shifted
+    pub fn to_bytes(&self) -> TagResult<Vec<u8>> {
+        let moov_end = self.moov_offset + self.moov_len;
+        let mut moov = self.moov.to_bytes();
+        let free_len = match atom_header(&self.bytes[moov_end..]) {
+            Ok((kind, _, len)) if &kind == b"free" => len,
+            _ => 0,
+        };
+        let available = self.moov_len + free_len;
+        let (padding, rest) = match available.checked_sub(moov.len()) {
+            Some(padding) if padding == 0 || padding >= ATOM_HEADER_LEN => {
+                (padding, moov_end + free_len)
+            }
+            _ => {
+                let delta = moov.len() as i64 - self.moov_len as i64;
+                let mut shifted = self.moov.clone();
+                let () = shifted.shift_chunk_offsets(moov_end as u64, delta)?;
+                moov = shifted.to_bytes();
+                (0, moov_end)
+            }
+        };
+        let mut bytes = Vec::with_capacity(self.bytes.len() + moov.len());
+        bytes.extend_from_slice(&self.bytes[..self.moov_offset]);
+        bytes.extend_from_slice(&moov);
+        if padding > 0 {
+            bytes.extend_from_slice(
+                &Atom::leaf(*b"free", vec![0; padding - ATOM_HEADER_LEN]).to_bytes(),
+            );
+        }
+        bytes.extend_from_slice(&self.bytes[rest..]);
+        Ok(bytes)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> TagResult<()> {
+        write_atomic(path, &self.to_bytes()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Atom, AtomBody, Mp4File};
+    use crate::libs::tags::tests::scratch_dir;
+
+    const CHUNKS: [&[u8]; 2] = [b"chunk #0", b"chunk #1"];
+
+    fn full_box(version_flags: u32, entries: &[u64], width: usize) -> Vec<u8> {
+        let mut data = version_flags.to_be_bytes().to_vec();
+        data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        for entry in entries {
+            data.extend_from_slice(&entry.to_be_bytes()[8 - width..])
+        }
+        data
+    }
+
+    /// A track whose chunks are listed in a `stco` table and another one using `co64`
+    fn moov(offsets: &[u64]) -> Atom {
+        let trak = |kind: [u8; 4], width: usize, offset: u64| {
+            let stbl = Atom::container(
+                *b"stbl",
+                vec![],
+                vec![Atom::leaf(kind, full_box(0, &[offset], width))],
+            );
+            let minf = Atom::container(*b"minf", vec![], vec![stbl]);
+            let mdia = Atom::container(*b"mdia", vec![], vec![minf]);
+            Atom::container(*b"trak", vec![], vec![mdia])
+        };
+        Atom::container(
+            *b"moov",
+            vec![],
+            vec![
+                Atom::leaf(*b"mvhd", vec![0; 100]),
+                trak(*b"stco", 4, offsets[0]),
+                trak(*b"co64", 8, offsets[1]),
+            ],
+        )
+    }
+
+    /// Layout of a file, `free` is the size of the free atom following moov
+    fn mp4_bytes(moov_first: bool, free: usize) -> Vec<u8> {
+        let ftyp = Atom::leaf(*b"ftyp", b"M4A \0\0\0\0".to_vec()).to_bytes();
+        let mdat = Atom::leaf(*b"mdat", CHUNKS.concat()).to_bytes();
+        let moov_len = moov(&[0, 0]).to_bytes().len();
+        let mdat_offset = match moov_first {
+            true => ftyp.len() + moov_len + free,
+            false => ftyp.len(),
+        } as u64;
+        let data_offset = mdat_offset + 8;
+        let moov = moov(&[data_offset, data_offset + CHUNKS[0].len() as u64]).to_bytes();
+        let mut bytes = ftyp;
+        if !moov_first {
+            bytes.extend_from_slice(&mdat)
+        }
+        bytes.extend_from_slice(&moov);
+        if free > 0 {
+            bytes.extend_from_slice(&Atom::leaf(*b"free", vec![0; free - 8]).to_bytes());
+        }
+        if moov_first {
+            bytes.extend_from_slice(&mdat)
+        }
+        bytes
+    }
+
+    fn chunk_offsets(atom: &Atom, offsets: &mut Vec<u64>) {
+        match &atom.body {
+            AtomBody::Container { children, .. } => children
+                .iter()
+                .for_each(|child| chunk_offsets(child, offsets)),
+            AtomBody::Leaf(data) if &atom.kind == b"stco" || &atom.kind == b"co64" => {
+                let width = if &atom.kind == b"stco" { 4 } else { 8 };
+                for entry in data[8..].chunks_exact(width) {
+                    offsets.push(entry.iter().fold(0, |acc, byte| (acc << 8) | *byte as u64))
+                }
+            }
+            AtomBody::Leaf(_) => (),
+        }
+    }
+
+    /// Chunk offsets of the file after the edit, checked against the content of mdat
+    fn edit(bytes: Vec<u8>) -> (Vec<u8>, Vec<u64>) {
+        let mut mp4 = Mp4File::parse(bytes).unwrap();
+        let () = mp4.set_text(b"\xA9nam", "One More Time").unwrap();
+        let () = mp4.set_freeform("initialkey", "Am").unwrap();
+        let bytes = mp4.to_bytes().unwrap();
+        let parsed = Mp4File::parse(bytes.clone()).unwrap();
+        let mut offsets = vec![];
+        let () = chunk_offsets(&parsed.moov, &mut offsets);
+        for (offset, chunk) in offsets.iter().zip(CHUNKS) {
+            let offset = *offset as usize;
+            assert_eq!(&bytes[offset..offset + chunk.len()], chunk);
+        }
+        (bytes, offsets)
+    }
+
+    fn offsets(bytes: Vec<u8>) -> Vec<u64> {
+        let mut offsets = vec![];
+        let () = chunk_offsets(&Mp4File::parse(bytes).unwrap().moov, &mut offsets);
+        offsets
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = mp4_bytes(true, 0);
+        assert_eq!(
+            Mp4File::parse(bytes.clone()).unwrap().to_bytes().unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn moov_before_mdat_grows() {
+        let original = mp4_bytes(true, 0);
+        let (bytes, new_offsets) = edit(original.clone());
+        let delta = (bytes.len() - original.len()) as u64;
+        let old_offsets = offsets(original);
+        assert_eq!(
+            new_offsets,
+            vec![old_offsets[0] + delta, old_offsets[1] + delta]
+        );
+    }
+
+    #[test]
+    fn grow_in_free() {
+        let original = mp4_bytes(true, 1024);
+        let (bytes, new_offsets) = edit(original.clone());
+        assert_eq!(bytes.len(), original.len());
+        assert_eq!(new_offsets, offsets(original));
+    }
+
+    #[test]
+    fn grow_beyond_free() {
+        let original = mp4_bytes(true, 16);
+        let (bytes, new_offsets) = edit(original.clone());
+        assert!(bytes.len() > original.len());
+        assert_ne!(new_offsets, offsets(original));
+    }
+
+    #[test]
+    fn moov_after_mdat() {
+        let original = mp4_bytes(false, 0);
+        let (bytes, new_offsets) = edit(original.clone());
+        assert!(bytes.len() > original.len());
+        assert_eq!(new_offsets, offsets(original));
+    }
+
+    #[test]
+    fn write() {
+        let directory = scratch_dir("mp4_write");
+        let path = directory.join("song.m4a");
+        let () = std::fs::write(&path, mp4_bytes(true, 0)).unwrap();
+        let mut mp4 = Mp4File::from_path(&path).unwrap();
+        let () = mp4.set_disc(2).unwrap();
+        let () = mp4.write(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), mp4.to_bytes().unwrap());
+    }
+}
@@ -0,0 +1,533 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::path::Path;
+
+use super::{vorbis::VorbisComment, write_atomic, TagError, TagResult};
+
+const OGG_MAGIC: &[u8; 4] = b"OggS";
+const PAGE_HEADER_LEN: usize = 27;
+const MAX_SEGMENTS: usize = 255;
+/// Header type flags
+const CONTINUED: u8 = 0x01;
+const BEGIN_OF_STREAM: u8 = 0x02;
+/// Granule position of a page where no packet ends
+const NO_GRANULE: u64 = u64::MAX;
+
+const VORBIS_ID: &[u8; 7] = b"\x01vorbis";
+const VORBIS_COMMENT: &[u8; 7] = b"\x03vorbis";
+const OPUS_ID: &[u8; 8] = b"OpusHead";
+const OPUS_COMMENT: &[u8; 8] = b"OpusTags";
+
+/// Crc of the ogg pages: polynomial 0x04c11db7, no reflection, no final xor
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = (index as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 0x8000_0000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x04C1_1DB7,
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, byte| {
+        (crc << 8) ^ CRC_TABLE[(((crc >> 24) as u8) ^ byte) as usize]
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OggCodec {
+    Vorbis,
+    Opus,
+}
+
+impl OggCodec {
+    fn from_id_header(packet: &[u8]) -> TagResult<Self> {
+        if packet.starts_with(VORBIS_ID) {
+            Ok(Self::Vorbis)
+        } else if packet.starts_with(OPUS_ID) {
+            Ok(Self::Opus)
+        } else {
+            Err(TagError::Unsupported("ogg stream neither vorbis nor opus"))
+        }
+    }
+
+    /// Identification, comment and, for vorbis, setup headers
+    fn header_count(&self) -> usize {
+        match self {
+            OggCodec::Vorbis => 3,
+            OggCodec::Opus => 2,
+        }
+    }
+
+    fn comment_magic(&self) -> &'static [u8] {
+        match self {
+            OggCodec::Vorbis => VORBIS_COMMENT,
+            OggCodec::Opus => OPUS_COMMENT,
+        }
+    }
+}
+
+struct Page {
+    header_type: u8,
+    granule: u64,
+    serial: u32,
+    sequence: u32,
+    lacing: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl Page {
+    /// Parse the page at the start of `bytes` and return its length
+    fn parse(bytes: &[u8]) -> TagResult<(Self, usize)> {
+        let header = bytes
+            .get(..PAGE_HEADER_LEN)
+            .ok_or(TagError::Malformed("truncated ogg page"))?;
+        if !header.starts_with(OGG_MAGIC) || header[4] != 0 {
+            return Err(TagError::Malformed("missing OggS capture pattern"));
+        }
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                header[offset],
+                header[offset + 1],
+                header[offset + 2],
+                header[offset + 3],
+            ])
+        };
+        let mut granule = [0; 8];
+        granule.copy_from_slice(&header[6..14]);
+        let segments = header[26] as usize;
+        let lacing = bytes
+            .get(PAGE_HEADER_LEN..PAGE_HEADER_LEN + segments)
+            .ok_or(TagError::Malformed("truncated ogg page"))?
+            .to_vec();
+        let start = PAGE_HEADER_LEN + segments;
+        let len = lacing.iter().map(|value| *value as usize).sum::<usize>();
+        let data = bytes
+            .get(start..start + len)
+            .ok_or(TagError::Malformed("truncated ogg page"))?
+            .to_vec();
+        let page = Self {
+            header_type: header[5],
+            granule: u64::from_le_bytes(granule),
+            serial: u32_at(14),
+            sequence: u32_at(18),
+            lacing,
+            data,
+        };
+        Ok((page, start + len))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PAGE_HEADER_LEN + self.lacing.len() + self.data.len());
+        bytes.extend_from_slice(OGG_MAGIC);
+        bytes.push(0);
+        bytes.push(self.header_type);
+        bytes.extend_from_slice(&self.granule.to_le_bytes());
+        bytes.extend_from_slice(&self.serial.to_le_bytes());
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        // Crc, computed with the field set to zero
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.push(self.lacing.len() as u8);
+        bytes.extend_from_slice(&self.lacing);
+        bytes.extend_from_slice(&self.data);
+        let crc = crc32(&bytes).to_le_bytes();
+        bytes[22..26].copy_from_slice(&crc);
+        bytes
+    }
+
+    /// Whether the last packet of the page ends on it
+    fn ends_packet(&self) -> bool {
+        matches!(self.lacing.last(), Some(value) if *value < 255)
+    }
+}
+
+/// Ogg vorbis or opus file, with its header packets reassembled
+pub struct OggFile {
+    codec: OggCodec,
+    serial: u32,
+    headers: Vec<Vec<u8>>,
+    /// Pages following the header pages, the audio of the stream
+    pages: Vec<Page>,
+    /// Number of pages which held the header packets
+    header_pages: u32,
+}
+
+impl OggFile {
+    pub fn parse(bytes: &[u8]) -> TagResult<Self> {
+        let mut pages = vec![];
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let (page, len) = Page::parse(&bytes[offset..])?;
+            pages.push(page);
+            offset += len;
+        }
+        let first = pages.first().ok_or(TagError::Malformed("empty ogg file"))?;
+        if first.header_type & BEGIN_OF_STREAM == 0 {
+            return Err(TagError::Malformed("missing ogg begin of stream"));
+        }
+        let serial = first.serial;
+        let mut headers: Vec<Vec<u8>> = vec![];
+        let mut packet = vec![];
+        let mut codec = None;
+        let mut header_pages = 0;
+        for page in pages.iter() {
+            if page.serial != serial {
+                return Err(TagError::Unsupported("multiplexed ogg streams"));
+            }
+            header_pages += 1;
+            let mut offset = 0;
+            for value in page.lacing.iter() {
+                let end = offset + *value as usize;
+                packet.extend_from_slice(&page.data[offset..end]);
+                offset = end;
+                if *value < 255 {
+                    headers.push(std::mem::take(&mut packet));
+                }
+            }
+            if codec.is_none() && !headers.is_empty() {
+                codec = Some(OggCodec::from_id_header(&headers[0])?)
+            }
+            if let Some(codec) = codec {
+                if headers.len() > codec.header_count() {
+                    return Err(TagError::Unsupported(
+                        "audio data on the last ogg header page",
+                    ));
+                }
+                if headers.len() == codec.header_count() && packet.is_empty() {
+                    break;
+                }
+            }
+        }
+        let codec = codec.ok_or(TagError::Malformed("truncated ogg headers"))?;
+        if headers.len() < codec.header_count() || !packet.is_empty() {
+            return Err(TagError::Malformed("truncated ogg headers"));
+        }
+        let pages = pages.split_off(header_pages as usize);
+        Ok(Self {
+            codec,
+            serial,
+            headers,
+            pages,
+            header_pages,
+        })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> TagResult<Self> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    pub fn vorbis_comment(&self) -> TagResult<VorbisComment> {
+        let magic = self.codec.comment_magic();
+        match self.headers[1].strip_prefix(magic) {
+            Some(data) => VorbisComment::parse(data),
+            None => Err(TagError::Malformed("missing ogg comment header")),
+        }
+    }
+
+    /// Replace the comment header.
+    /// The vorbis framing bit and the opus data following the comment are kept
+    pub fn set_vorbis_comment(&mut self, comment: &VorbisComment) -> TagResult<()> {
+        let magic = self.codec.comment_magic();
+        let data = self.headers[1]
+            .strip_prefix(magic)
+            .ok_or(TagError::Malformed("missing ogg comment header"))?;
+        let (_, len) = VorbisComment::parse_len(data)?;
+        let trailing = data[len..].to_vec();
+        let mut packet = magic.to_vec();
+        packet.extend_from_slice(&comment.to_bytes());
+        packet.extend_from_slice(&trailing);
+        self.headers[1] = packet;
+        Ok(())
+    }
+
+    /// Lay the header packets out on pages, the identification header alone on the first one
+    fn header_pages(&self) -> Vec<Page> {
+        let mut pages: Vec<Page> = vec![];
+        for (index, packet) in self.headers.iter().enumerate() {
+            let mut lacing = vec![255; packet.len() / 255];
+            lacing.push((packet.len() % 255) as u8);
+            let mut offset = 0;
+            for (chunk_index, chunk) in lacing.chunks(MAX_SEGMENTS).enumerate() {
+                let len = chunk.iter().map(|value| *value as usize).sum::<usize>();
+                let data = &packet[offset..offset + len];
+                offset += len;
+                let continued = chunk_index > 0;
+                let page = match pages.last_mut() {
+                    // The following headers share the pages as long as the segments fit
+                    Some(page)
+                        if index > 1
+                            && !continued
+                            && page.lacing.len() + chunk.len() <= MAX_SEGMENTS =>
+                    {
+                        page
+                    }
+                    _ => {
+                        pages.push(Page {
+                            header_type: match (index, continued) {
+                                (0, _) => BEGIN_OF_STREAM,
+                                (_, true) => CONTINUED,
+                                (_, false) => 0,
+                            },
+                            granule: 0,
+                            serial: self.serial,
+                            sequence: pages.len() as u32,
+                            lacing: vec![],
+                            data: vec![],
+                        });
+                        pages.last_mut().unwrap()
+                    }
+                };
+                page.lacing.extend_from_slice(chunk);
+                page.data.extend_from_slice(data);
+            }
+        }
+        for page in pages.iter_mut() {
+            if !page.ends_packet() {
+                page.granule = NO_GRANULE
+            }
+        }
+        pages
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header_pages = self.header_pages();
+        let shift = header_pages.len() as i64 - self.header_pages as i64;
+        let mut bytes = vec![];
+        for page in header_pages.iter() {
+            bytes.extend_from_slice(&page.to_bytes())
+        }
+        for page in self.pages.iter() {
+            match page.serial == self.serial && shift != 0 {
+                true => {
+                    let sequence = (page.sequence as i64 + shift) as u32;
+                    let page = Page {
+                        sequence,
+                        lacing: page.lacing.clone(),
+                        data: page.data.clone(),
+                        ..*page
+                    };
+                    bytes.extend_from_slice(&page.to_bytes())
+                }
+                false => bytes.extend_from_slice(&page.to_bytes()),
+            }
+        }
+        bytes
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> TagResult<()> {
+        write_atomic(path, &self.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, OggCodec, OggFile, Page, CONTINUED, NO_GRANULE, OPUS_COMMENT};
+    use crate::libs::tags::{tests::scratch_dir, vorbis::VorbisComment};
+
+    const SERIAL: u32 = 0x1234;
+
+    /// Page holding an opus identification header, its crc checked with an independent implementation
+    const OPUS_ID_PAGE: &str = "4f6767530002000000000000000034120000000000000650fbab01134f707573\
+                                486561640102000000000000000000";
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&s[index..index + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn comment_packet(magic: &[u8], comment: &VorbisComment, trailing: &[u8]) -> Vec<u8> {
+        let mut packet = magic.to_vec();
+        packet.extend_from_slice(&comment.to_bytes());
+        packet.extend_from_slice(trailing);
+        packet
+    }
+
+    fn audio_pages(first_sequence: u32) -> Vec<Page> {
+        (0..3)
+            .map(|index| Page {
+                header_type: 0,
+                granule: 960 * (index as u64 + 1),
+                serial: SERIAL,
+                sequence: first_sequence + index,
+                lacing: vec![10],
+                data: vec![index as u8; 10],
+            })
+            .collect()
+    }
+
+    /// Vorbis stream whose comment and setup headers share the second page
+    fn vorbis_file() -> OggFile {
+        let mut comment = VorbisComment::default();
+        let () = comment.set("TITLE", "Digital Love");
+        let mut id = b"\x01vorbis".to_vec();
+        id.extend_from_slice(&[0; 23]);
+        let mut setup = b"\x05vorbis".to_vec();
+        setup.extend_from_slice(&[0xAA; 40]);
+        OggFile {
+            codec: OggCodec::Vorbis,
+            serial: SERIAL,
+            // The framing bit ends the comment header
+            headers: vec![id, comment_packet(b"\x03vorbis", &comment, &[1]), setup],
+            pages: audio_pages(2),
+            header_pages: 2,
+        }
+    }
+
+    fn pages(bytes: &[u8]) -> Vec<Page> {
+        let mut pages = vec![];
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let (page, len) = Page::parse(&bytes[offset..]).unwrap();
+            pages.push(page);
+            offset += len;
+        }
+        pages
+    }
+
+    #[test]
+    fn crc() {
+        // Check value of the crc-32/mpeg-2 parameters without the initial and final xor
+        assert_eq!(crc32(b"123456789"), 0x89A1_897F);
+        let bytes = hex(OPUS_ID_PAGE);
+        let (page, len) = Page::parse(&bytes).unwrap();
+        assert_eq!(len, bytes.len());
+        assert_eq!(page.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = vorbis_file().to_bytes();
+        let ogg = OggFile::parse(&bytes).unwrap();
+        assert_eq!(ogg.header_pages, 2);
+        assert_eq!(ogg.headers, vorbis_file().headers);
+        assert_eq!(ogg.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn comment_spanning_several_pages() {
+        let mut ogg = OggFile::parse(&vorbis_file().to_bytes()).unwrap();
+        let mut comment = ogg.vorbis_comment().unwrap();
+        // Larger than the 255 segments of 255 bytes of a page
+        let () = comment.set("COMMENT", &"x".repeat(3 * 255 * 255));
+        let () = ogg.set_vorbis_comment(&comment).unwrap();
+        let bytes = ogg.to_bytes();
+
+        let pages = pages(&bytes);
+        // The identification, four comment pages, the last one with the setup, and the audio pages
+        assert_eq!(pages.len(), 1 + 4 + 3);
+        for (sequence, page) in pages.iter().enumerate() {
+            assert_eq!(page.sequence, sequence as u32);
+        }
+        for page in pages[1..4].iter() {
+            assert_eq!(page.lacing.len(), 255);
+            assert_eq!(page.granule, NO_GRANULE);
+        }
+        for page in pages[2..5].iter() {
+            assert_eq!(page.header_type, CONTINUED);
+        }
+        assert_eq!(pages[4].lacing.len(), 2);
+        assert_eq!(pages[4].granule, 0);
+        for (page, audio) in pages[5..].iter().zip(audio_pages(0)) {
+            assert_eq!(page.data, audio.data);
+            assert_eq!(page.granule, audio.granule);
+        }
+
+        let parsed = OggFile::parse(&bytes).unwrap();
+        assert_eq!(parsed.header_pages, 5);
+        assert_eq!(
+            parsed.vorbis_comment().unwrap().to_bytes(),
+            comment.to_bytes()
+        );
+        // The framing bit is still there
+        assert_eq!(parsed.headers[1].last(), Some(&1));
+        assert_eq!(parsed.headers[2], vorbis_file().headers[2]);
+    }
+
+    #[test]
+    fn sequences_shift_back() {
+        let mut file = vorbis_file();
+        let mut comment = VorbisComment::default();
+        let () = comment.set("COMMENT", &"x".repeat(70_000));
+        file.headers[1] = comment_packet(b"\x03vorbis", &comment, &[1]);
+        let mut ogg = OggFile::parse(&file.to_bytes()).unwrap();
+        assert!(ogg.header_pages > 2);
+        let () = ogg.set_vorbis_comment(&VorbisComment::default()).unwrap();
+        let pages = pages(&ogg.to_bytes());
+        assert_eq!(pages.len(), 2 + 3);
+        for (sequence, page) in pages.iter().enumerate() {
+            assert_eq!(page.sequence, sequence as u32);
+        }
+    }
+
+    #[test]
+    fn opus_padding() {
+        let mut comment = VorbisComment::default();
+        let () = comment.set("TITLE", "Veridis Quo");
+        let padding = b"\x01binary data kept by the encoder";
+        let mut id = b"OpusHead".to_vec();
+        id.extend_from_slice(&[1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let file = OggFile {
+            codec: OggCodec::Opus,
+            serial: SERIAL,
+            headers: vec![id, comment_packet(OPUS_COMMENT, &comment, padding)],
+            pages: audio_pages(2),
+            header_pages: 2,
+        };
+        let mut ogg = OggFile::parse(&file.to_bytes()).unwrap();
+        assert_eq!(ogg.codec, OggCodec::Opus);
+        let () = comment.set("ARTIST", "Daft Punk");
+        let () = ogg.set_vorbis_comment(&comment).unwrap();
+        let parsed = OggFile::parse(&ogg.to_bytes()).unwrap();
+        assert_eq!(
+            parsed.headers[1],
+            comment_packet(OPUS_COMMENT, &comment, padding)
+        );
+    }
+
+    #[test]
+    fn write() {
+        let directory = scratch_dir("ogg_write");
+        let path = directory.join("song.ogg");
+        let () = std::fs::write(&path, vorbis_file().to_bytes()).unwrap();
+        let mut ogg = OggFile::from_path(&path).unwrap();
+        let mut comment = ogg.vorbis_comment().unwrap();
+        let () = comment.set("INITIALKEY", "Am");
+        let () = ogg.set_vorbis_comment(&comment).unwrap();
+        let () = ogg.write(&path).unwrap();
+        let parsed = OggFile::from_path(&path).unwrap();
+        assert_eq!(
+            parsed.vorbis_comment().unwrap().to_bytes(),
+            comment.to_bytes()
+        );
+        assert_eq!(parsed.pages.len(), 3);
+    }
+}
@@ -0,0 +1,138 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+use std::path::Path;
+
+use super::{TagError, TagResult};
+
+/// Field holding a base64 flac picture block
+const PICTURE_FIELD: &str = "METADATA_BLOCK_PICTURE";
+/// Picture type of a front cover in a flac picture block
+const FRONT_COVER: u32 = 3;
+
+/// Vorbis comment, shared by flac, ogg vorbis and opus
+#[derive(Debug, Clone, Default)]
+pub struct VorbisComment {
+    vendor: String,
+    comments: Vec<(String, String)>,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> TagResult<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or(TagError::Malformed("truncated vorbis comment"))
+}
+
+impl VorbisComment {
+    /// Parse the comment without any framing bit or header packet type
+    pub fn parse(data: &[u8]) -> TagResult<Self> {
+        Self::parse_len(data).map(|(comment, _)| comment)
+    }
+
+    /// Parse the comment and return the number of bytes read
+    pub fn parse_len(data: &[u8]) -> TagResult<(Self, usize)> {
+        let vendor_len = read_u32(data, 0)? as usize;
+        let vendor = data
+            .get(4..4 + vendor_len)
+            .ok_or(TagError::Malformed("truncated vorbis vendor"))?;
+        let vendor = String::from_utf8_lossy(vendor).to_string();
+        let mut offset = 4 + vendor_len;
+        let count = read_u32(data, offset)?;
+        offset += 4;
+        let mut comments = vec![];
+        for _ in 0..count {
+            let len = read_u32(data, offset)? as usize;
+            offset += 4;
+            let comment = data
+                .get(offset..offset + len)
+                .ok_or(TagError::Malformed("truncated vorbis comment"))?;
+            offset += len;
+            let comment = String::from_utf8_lossy(comment);
+            if let Some((key, value)) = comment.split_once('=') {
+                comments.push((key.to_string(), value.to_string()))
+            }
+        }
+        Ok((Self { vendor, comments }, offset))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&(self.vendor.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.vendor.as_bytes());
+        bytes.extend_from_slice(&(self.comments.len() as u32).to_le_bytes());
+        for (key, value) in self.comments.iter() {
+            let comment = format!("{}={}", key, value);
+            bytes.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(comment.as_bytes());
+        }
+        bytes
+    }
+
+    /// Replace all the values of the field, field names are case insensitive
+    pub fn set(&mut self, key: &str, value: &str) {
+        let () = self.remove(key);
+        self.comments.push((key.to_uppercase(), value.to_string()))
+    }
+
+    /// Add a value to the field, keeping the existing ones
+    pub fn add(&mut self, key: &str, value: &str) {
+        self.comments.push((key.to_uppercase(), value.to_string()))
+    }
+
+    /// Add the image as a front cover flac picture block
+    pub fn add_picture<P: AsRef<Path>>(&mut self, path: P) -> TagResult<()> {
+        let block = picture_block(path.as_ref())?;
+        let () = self.add(PICTURE_FIELD, &base64::encode(block));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.comments
+            .retain(|(comment_key, _)| !comment_key.eq_ignore_ascii_case(key))
+    }
+}
+
+/// Flac picture block of an image file, the format and the dimensions are read from the image
+fn picture_block(path: &Path) -> TagResult<Vec<u8>> {
+    let data = std::fs::read(path)?;
+    let reader = image::io::Reader::new(std::io::Cursor::new(&data)).with_guessed_format()?;
+    let mime = match reader.format() {
+        Some(image::ImageFormat::Png) => "image/png",
+        Some(image::ImageFormat::Jpeg) => "image/jpeg",
+        Some(image::ImageFormat::Gif) => "image/gif",
+        Some(image::ImageFormat::Bmp) => "image/bmp",
+        Some(image::ImageFormat::WebP) => "image/webp",
+        _ => return Err(TagError::Unsupported("unknown image format")),
+    };
+    let (width, height) = reader
+        .into_dimensions()
+        .map_err(|_| TagError::Malformed("unreadable image"))?;
+    let mut block = vec![];
+    block.extend_from_slice(&FRONT_COVER.to_be_bytes());
+    block.extend_from_slice(&(mime.len() as u32).to_be_bytes());
+    block.extend_from_slice(mime.as_bytes());
+    // Empty description
+    block.extend_from_slice(&0u32.to_be_bytes());
+    block.extend_from_slice(&width.to_be_bytes());
+    block.extend_from_slice(&height.to_be_bytes());
+    // Color depth and number of indexed colors
+    block.extend_from_slice(&24u32.to_be_bytes());
+    block.extend_from_slice(&0u32.to_be_bytes());
+    block.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    block.extend_from_slice(&data);
+    Ok(block)
+}
@@ -17,12 +17,14 @@
 
 use std::cmp;
 
-use chrono::Datelike;
 use cuesheet_rs::{CueFileFormat, CueSheet, CueTrack, DurationFormat};
 use image::DynamicImage;
 use viuer::Config;
 
-use super::spotify;
+use super::{
+    output::{self, CueTrackRecord, OutputFormat},
+    spotify,
+};
 
 pub fn convert_mille_to_duration(milliemes: u64) -> DurationFormat {
     let secondes = milliemes / 1000;
@@ -32,6 +34,16 @@ pub fn convert_mille_to_duration(milliemes: u64) -> DurationFormat {
     DurationFormat::MinSecMil(minutes as u32, secondes as u32, milliemes as u32)
 }
 
+/// Duration as m:ss, or h:mm:ss for more than an hour
+pub fn format_duration(milliemes: u64) -> String {
+    let secondes = milliemes / 1000;
+    let (hours, minutes, secondes) = (secondes / 3600, (secondes / 60) % 60, secondes % 60);
+    match hours {
+        0 => format!("{}:{:02}", minutes, secondes),
+        _ => format!("{}:{:02}:{:02}", hours, minutes, secondes),
+    }
+}
+
 fn min_sec_mil_of_millies(milliemes: u64) -> (u32, u32, u32) {
     let secondes = milliemes / 1000;
     let milliemes = milliemes % 1000;
@@ -158,13 +170,115 @@ pub(crate) async fn display_related_artist(
     Some(())
 }
 
-pub async fn cuesheet_from_album_id(
+/// Print the cells of a row of albums, each column being 60 characters wide
+fn print_discography_row(cells: Vec<String>) {
+    for cell in cells {
+        let space_len = if cell.chars().count() > 60 {
+            5
+        } else {
+            60 - cell.chars().count()
+        };
+        print!("{}", cell);
+        (0..space_len).for_each(|_| print!(" "));
+    }
+    println!("");
+}
+
+/// Display the albums grouped by their album group, in columns
+pub(crate) async fn display_discography(
+    albums: &[spotify::AlbumItems],
+    groups: &[spotify::SpotifyIncludeGroupe],
+    column: usize,
+    graphic: bool,
+) {
+    for group in spotify::SpotifyIncludeGroupe::ALL.iter() {
+        if !groups.contains(group) {
+            continue;
+        }
+        let group_albums = albums
+            .iter()
+            .filter(|album| album.group().as_ref() == Some(group))
+            .collect::<Vec<_>>();
+        if group_albums.is_empty() {
+            continue;
+        }
+        println!("\n****   {} ({})\n", group.title(), group_albums.len());
+        display_album_grid(&group_albums, column, false, graphic).await
+    }
+}
+
+/// Display the albums in columns, with their artists if `with_artists` is set
+pub(crate) async fn display_album_grid(
+    albums: &[&spotify::AlbumItems],
+    column: usize,
+    with_artists: bool,
+    graphic: bool,
+) {
+    let column = cmp::max(column, 1);
+    for chunk in albums.chunks(column) {
+        print_discography_row(
+            chunk
+                .iter()
+                .map(|album| format!("Name   : {}", album.name))
+                .collect(),
+        );
+        if with_artists {
+            print_discography_row(
+                chunk
+                    .iter()
+                    .map(|album| format!("Artist : {}", album.artists_name()))
+                    .collect(),
+            );
+        }
+        print_discography_row(
+            chunk
+                .iter()
+                .map(|album| format!("Date   : {}", album.release_date))
+                .collect(),
+        );
+        print_discography_row(
+            chunk
+                .iter()
+                .map(|album| format!("Tracks : {}", album.total_tracks))
+                .collect(),
+        );
+        print_discography_row(
+            chunk
+                .iter()
+                .map(|album| format!("Id     : {}", album.id))
+                .collect(),
+        );
+        if graphic {
+            for (i, album) in chunk.iter().enumerate() {
+                if let Some(image) = album.dynamic_image().await {
+                    show_image_config(
+                        &image,
+                        &Config {
+                            absolute_offset: false,
+                            x: (i * 60) as u16,
+                            y: 0,
+                            // Keep the cursor on the row until its last cover
+                            restore_cursor: i + 1 < chunk.len(),
+                            width: Some(50),
+                            height: None,
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+        }
+        println!("");
+    }
+}
+
+pub async fn cuesheet_from_album(
     filename: String,
     format: CueFileFormat,
     output: Option<String>,
-    album_id: &str,
+    album: &spotify::Album,
     total_duration: bool,
     image: Option<String>,
+    output_format: OutputFormat,
 ) -> Result<(), String> {
     // let mut argv = env::args().collect::<Vec<String>>();
     // let mut mapper_argv = argv.
@@ -174,11 +288,6 @@ pub async fn cuesheet_from_album_id(
     // mapper_argv.push("\0".as_ptr() as *mut i8);
 
     let mut total = 0u64;
-    let spotify = spotify::Spotify::init().await;
-    let album = spotify
-        .album(album_id.to_string())
-        .await
-        .ok_or("Unable to fetch the album".to_string())?;
 
     if let Some(image_path) = image {
         if let Some(map) = album.images.get(0) {
@@ -203,7 +312,7 @@ pub async fn cuesheet_from_album_id(
         cue_sheet.add_performer(album_artist.as_str());
     }
 
-    if let Some(Some(genres)) = album.genres {
+    if let Some(Some(genres)) = &album.genres {
         if !genres.is_empty() {
             let str_genres = genres.join(", ");
 
@@ -211,9 +320,11 @@ pub async fn cuesheet_from_album_id(
         }
     }
 
+    // Players expect a year in REM DATE
     let str_date = album.release_date.year().to_string();
     cue_sheet.add_rem("DATE", str_date.as_str());
 
+    let mut records = vec![];
     album.tracks.items.iter().for_each(|track| {
         let mut cuetrack =
             CueTrack::new(track.track_number as u32, cuesheet_rs::CueTrackMode::AUDIO);
@@ -227,6 +338,18 @@ pub async fn cuesheet_from_album_id(
                 .join(", ");
             let _ = cuetrack.add_performer(&str_artist.as_str());
         }
+        records.push(CueTrackRecord {
+            number: track.track_number as u32,
+            title: track.name.clone(),
+            performer: track
+                .artists
+                .iter()
+                .map(|artist| artist.name.to_owned())
+                .collect::<Vec<String>>()
+                .join(", "),
+            start_ms: total,
+            duration_ms: track.duration_ms,
+        });
         total += track.duration_ms;
         let c_track_name = track.name.clone();
         cuetrack.add_index(1, convert_mille_to_duration(track.duration_ms));
@@ -246,6 +369,7 @@ pub async fn cuesheet_from_album_id(
         Some(output_path) => cue_sheet
             .export(true, output_path)
             .map_err(|e| format!("{}", e))?,
+        None if !output_format.is_text() => output::print_records(output_format, &records),
         None => println!("{}", cue_sheet.repr(true)),
     };
 
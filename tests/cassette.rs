@@ -0,0 +1,91 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+mod common;
+
+use common::{fixtures_dir, Koto, MockApi};
+
+fn cassette(name: &str) -> String {
+    fixtures_dir()
+        .join("cassettes")
+        .join(name)
+        .to_string_lossy()
+        .to_string()
+}
+
+#[test]
+fn replay_search_track() {
+    let koto = Koto::new("replay_search_track")
+        .env("KOTO_CASSETTE_MODE", "replay")
+        .env("KOTO_CASSETTE", &cassette("search_track.json"));
+    let stdout = koto.stdout(&["--no-cache", "search", "track", "one more time"]);
+    assert!(
+        stdout.contains("Song ID        : 0DiWol3AO6WpXZgp0goxAV"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Album          : Discovery (2001-03-12)"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn replay_search_album() {
+    let koto = Koto::new("replay_search_album")
+        .env("KOTO_CASSETTE_MODE", "replay")
+        .env("KOTO_CASSETTE", &cassette("search_album.json"));
+    let stdout = koto.stdout(&["--no-cache", "search", "album", "discovery"]);
+    assert!(
+        stdout.contains("Album ID      : 2noRn2Aes5aoNVsU6iWThc"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Label         : Parlophone (France)"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Aerodynamic"), "{}", stdout);
+}
+
+#[test]
+fn missing_cassette() {
+    let koto = Koto::new("missing_cassette")
+        .env("KOTO_CASSETTE_MODE", "replay")
+        .env("KOTO_CASSETTE", &cassette("missing.json"));
+    let output = koto.run(&["--no-cache", "search", "track", "one more time"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(8), "{:?}", output);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    assert!(stderr.contains("missing.json"), "{}", stderr);
+}
+
+#[test]
+fn record_without_token() {
+    let mock = MockApi::start();
+    let koto = Koto::new("record_without_token").with_mock(&mock).init();
+    let path = koto.home().join("cassette.json");
+    let koto = koto
+        .env("KOTO_CASSETTE_MODE", "record")
+        .env("KOTO_CASSETTE", &path.to_string_lossy());
+    let _ = koto.stdout(&["--no-cache", "search", "track", "one more time"]);
+    let recorded = std::fs::read_to_string(&path).expect("No cassette recorded");
+    assert!(recorded.contains("/v1/search?"), "{}", recorded);
+    assert!(!recorded.contains("access_token"), "{}", recorded);
+}
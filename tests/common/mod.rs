@@ -0,0 +1,183 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+//! Helpers running the koto binary against the fixtures mock server
+
+#![allow(dead_code)]
+
+use std::{
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Output, Stdio},
+};
+
+const KOTO: &str = env!("CARGO_BIN_EXE_koto");
+
+pub fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+}
+
+/// Json of a fixture, to derive the fixtures of a test from it
+pub fn read_fixture(path: &str) -> serde_json::Value {
+    let file = std::fs::File::open(fixtures_dir().join(path)).expect("Missing fixture");
+    serde_json::from_reader(file).expect("Invalid fixture")
+}
+
+/// Write a fixture of a test under `fixtures`, served by [MockApi::start_with]
+pub fn write_fixture(fixtures: &Path, path: &str, value: &serde_json::Value) {
+    let path = fixtures.join(path);
+    let () = std::fs::create_dir_all(path.parent().expect("Fixture directory"))
+        .expect("Cannot create the fixture directory");
+    let () = std::fs::write(path, value.to_string()).expect("Cannot write the fixture");
+}
+
+/// `koto mock-server` listening on a free port, killed on drop
+pub struct MockApi {
+    child: Child,
+    /// Kept open so that the server can keep printing
+    _stdout: BufReader<ChildStdout>,
+    accounts_url: String,
+}
+
+impl MockApi {
+    pub fn start() -> Self {
+        Self::start_with(&fixtures_dir())
+    }
+
+    /// Server answering with the fixtures of another directory
+    pub fn start_with(fixtures: &Path) -> Self {
+        let mut child = Command::new(KOTO)
+            .args(["mock-server", "--address", "127.0.0.1:0", "--fixtures"])
+            .arg(fixtures)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Cannot start the mock server");
+        let stdout = child.stdout.take().expect("Mock server stdout");
+        let mut stdout = BufReader::new(stdout);
+        let mut line = String::new();
+        let _ = stdout
+            .read_line(&mut line)
+            .expect("Cannot read the mock server url");
+        let accounts_url = line
+            .trim()
+            .strip_prefix("KOTO_ACCOUNTS_URL=")
+            .unwrap_or_else(|| panic!("Unexpected mock server output: {}", line))
+            .to_string();
+        Self {
+            child,
+            _stdout: stdout,
+            accounts_url,
+        }
+    }
+
+    pub fn accounts_url(&self) -> &str {
+        &self.accounts_url
+    }
+
+    pub fn api_url(&self) -> String {
+        format!("{}/v1", self.accounts_url)
+    }
+}
+
+impl Drop for MockApi {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Koto invocations sharing an isolated xdg home
+pub struct Koto {
+    home: PathBuf,
+    envs: Vec<(String, String)>,
+}
+
+impl Koto {
+    /// Fresh home named after the test
+    pub fn new(test: &str) -> Self {
+        let home = std::env::temp_dir().join("koto-tests").join(format!(
+            "{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home).expect("Cannot create the test home");
+        Self { home, envs: vec![] }
+    }
+
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.envs.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Point the api and accounts urls at the mock server
+    pub fn with_mock(self, mock: &MockApi) -> Self {
+        let api_url = mock.api_url();
+        self.env("KOTO_API_URL", &api_url)
+            .env("KOTO_ACCOUNTS_URL", mock.accounts_url())
+    }
+
+    /// `koto init` with fake credentials, it creates the artists db and the env file
+    pub fn init(self) -> Self {
+        let output = self.run(&["init", "--client-id", "id", "--client-secret", "secret"]);
+        assert!(output.status.success(), "koto init failed: {:?}", output);
+        self
+    }
+
+    pub fn run(&self, args: &[&str]) -> Output {
+        let mut command = Command::new(KOTO);
+        let _ = command
+            .args(args)
+            .env_remove("CLIENT_ID")
+            .env_remove("CLIENT_SECRET")
+            .env_remove("KOTO_CASSETTE")
+            .env_remove("KOTO_CASSETTE_MODE")
+            .env_remove("KOTO_API_URL")
+            .env_remove("KOTO_ACCOUNTS_URL")
+            .env("XDG_CONFIG_HOME", self.home.join("config"))
+            .env("XDG_DATA_HOME", self.home.join("data"))
+            .env("XDG_CACHE_HOME", self.home.join("cache"))
+            .stdin(Stdio::null());
+        for (key, value) in self.envs.iter() {
+            let _ = command.env(key, value);
+        }
+        command.output().expect("Cannot run koto")
+    }
+
+    /// Stdout of a successful run
+    pub fn stdout(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "koto {:?} failed\nstdout: {}\nstderr: {}",
+            args,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+}
+
+impl Drop for Koto {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.home);
+    }
+}
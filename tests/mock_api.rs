@@ -0,0 +1,313 @@
+// /////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                            //
+// This file is part of Koto: A holdall music program                                         //
+// Copyright (C) 2023 Yves Ndiaye                                                             //
+//                                                                                            //
+// Koto is free software: you can redistribute it and/or modify it under the terms            //
+// of the GNU General Public License as published by the Free Software Foundation,            //
+// either version 3 of the License, or (at your option) any later version.                    //
+//                                                                                            //
+// Koto is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;          //
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR           //
+// PURPOSE.  See the GNU General Public License for more details.                             //
+// You should have received a copy of the GNU General Public License along with Koto.         //
+// If not, see <http://www.gnu.org/licenses/>.                                                //
+//                                                                                            //
+// /////////////////////////////////////////////////////////////////////////////////////////////
+
+mod common;
+
+use common::{read_fixture, write_fixture, Koto, MockApi};
+
+#[test]
+fn search_artist() {
+    let mock = MockApi::start();
+    let koto = Koto::new("search_artist").with_mock(&mock).init();
+    let stdout = koto.stdout(&["search", "artist", "daft punk", "--first"]);
+    assert!(stdout.contains("Name  : Daft Punk"), "{}", stdout);
+    assert!(stdout.contains("french house"), "{}", stdout);
+}
+
+#[test]
+fn search_artist_as_json() {
+    let mock = MockApi::start();
+    let koto = Koto::new("search_artist_as_json").with_mock(&mock).init();
+    let stdout = koto.stdout(&[
+        "search",
+        "artist",
+        "daft punk",
+        "--first",
+        "--output-format",
+        "json",
+    ]);
+    let json = stdout
+        .find(['{', '['])
+        .map(|start| &stdout[start..])
+        .expect("No json in the output");
+    let value: serde_json::Value = serde_json::from_str(json).expect("Invalid json");
+    assert!(
+        value.to_string().contains("4tZwfgrHOc3mvqYlEYSvVN"),
+        "{}",
+        value
+    );
+}
+
+#[test]
+fn search_album() {
+    let mock = MockApi::start();
+    let koto = Koto::new("search_album").with_mock(&mock).init();
+    let stdout = koto.stdout(&["search", "album", "discovery"]);
+    assert!(stdout.contains("Album Name    : Discovery"), "{}", stdout);
+    assert!(
+        stdout.contains("Album ID      : 2noRn2Aes5aoNVsU6iWThc"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("One More Time"), "{}", stdout);
+    assert!(stdout.contains("Aerodynamic"), "{}", stdout);
+}
+
+#[test]
+fn search_track() {
+    let mock = MockApi::start();
+    let koto = Koto::new("search_track").with_mock(&mock).init();
+    let stdout = koto.stdout(&["search", "track", "one more time"]);
+    assert!(
+        stdout.contains("Song Name      : One More Time"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Song ID        : 0DiWol3AO6WpXZgp0goxAV"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("ISRC           : GBDUW0000053"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn search_track_features() {
+    let mock = MockApi::start();
+    let koto = Koto::new("search_track_features").with_mock(&mock).init();
+    let stdout = koto.stdout(&["search", "track", "one more time", "--features"]);
+    assert!(stdout.contains("Tempo          : 122.8 BPM"), "{}", stdout);
+}
+
+#[test]
+fn search_track_features_batches() {
+    let koto = Koto::new("search_track_features_batches");
+    let fixtures = koto.home().join("fixtures");
+    write_fixture(&fixtures, "api/token.json", &read_fixture("api/token.json"));
+    let mut search = read_fixture("v1/search/track.json");
+    let track = search["tracks"]["items"][0].clone();
+    let feature = read_fixture("v1/audio-features/0DiWol3AO6WpXZgp0goxAV.json");
+    // One request of 100 ids and one request on the single id endpoint
+    let ids = (0..101).map(|i| format!("{:022}", i)).collect::<Vec<_>>();
+    let mut tracks = vec![];
+    for (index, id) in ids.iter().enumerate() {
+        let mut track = track.clone();
+        track["id"] = id.as_str().into();
+        tracks.push(track);
+        let mut feature = feature.clone();
+        feature["id"] = id.as_str().into();
+        feature["tempo"] = (index as f64).into();
+        write_fixture(
+            &fixtures,
+            &format!("v1/audio-features/{}.json", id),
+            &feature,
+        );
+    }
+    search["tracks"]["items"] = tracks.into();
+    write_fixture(&fixtures, "v1/search/track.json", &search);
+
+    let mock = MockApi::start_with(&fixtures);
+    let koto = koto.with_mock(&mock).init();
+    let stdout = koto.stdout(&[
+        "search",
+        "track",
+        "one more time",
+        "--features",
+        "--output-format",
+        "json",
+    ]);
+    let records: Vec<serde_json::Value> = serde_json::from_str(&stdout).expect("Invalid json");
+    assert_eq!(records.len(), ids.len());
+    for (index, (record, id)) in records.iter().zip(ids.iter()).enumerate() {
+        assert_eq!(record["id"], id.as_str());
+        assert_eq!(record["tempo"], index as f64, "{}", record);
+    }
+}
+
+#[test]
+fn list_update() {
+    let mock = MockApi::start();
+    let koto = Koto::new("list_update").with_mock(&mock).init();
+    let _ = koto.stdout(&["list", "--add", "daft punk", "--first"]);
+    let output = koto.run(&["list", "--update"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("New realease"), "{}", stderr);
+    let output = koto.run(&["list", "--update", "--output-format", "json"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("New realease"), "{}", stderr);
+    let stdout = koto.stdout(&["list"]);
+    assert!(
+        stdout.contains("Artist ID     : 4tZwfgrHOc3mvqYlEYSvVN"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Last Album    : Discovery"), "{}", stdout);
+}
+
+#[test]
+fn cuesheet_fetch() {
+    let mock = MockApi::start();
+    let koto = Koto::new("cuesheet_fetch").with_mock(&mock).init();
+    let stdout = koto.stdout(&[
+        "cue-sheet",
+        "fetch",
+        "--album-id",
+        "2noRn2Aes5aoNVsU6iWThc",
+        "--format",
+        "wave",
+    ]);
+    assert!(stdout.contains("Discovery"), "{}", stdout);
+    assert!(stdout.contains("Daft Punk"), "{}", stdout);
+    assert!(stdout.contains("One More Time"), "{}", stdout);
+    assert!(stdout.contains("Aerodynamic"), "{}", stdout);
+    assert!(stdout.contains("REM DATE 2001"), "{}", stdout);
+    assert!(!stdout.contains("REM DATE 2001-"), "{}", stdout);
+}
+
+#[test]
+fn cuesheet_fetch_by_search() {
+    let mock = MockApi::start();
+    let koto = Koto::new("cuesheet_fetch_by_search")
+        .with_mock(&mock)
+        .init();
+    let stdout = koto.stdout(&[
+        "cue-sheet",
+        "fetch",
+        "--artist",
+        "daft punk",
+        "--album",
+        "discovery",
+        "--first",
+        "--format",
+        "wave",
+    ]);
+    assert!(stdout.contains("One More Time"), "{}", stdout);
+}
+
+#[test]
+fn cached_response_without_token() {
+    let mock = MockApi::start();
+    let koto = Koto::new("cached_response_without_token")
+        .with_mock(&mock)
+        .init();
+    let args = ["search", "artist", "--id", "4tZwfgrHOc3mvqYlEYSvVN"];
+    let _ = koto.stdout(&args);
+    // Neither the api nor a token are available anymore
+    drop(mock);
+    let () = std::fs::remove_file(koto.home().join("cache/koto/token.json"))
+        .expect("The token wasn't cached");
+    let stdout = koto.stdout(&args);
+    assert!(stdout.contains("Name  : Daft Punk"), "{}", stdout);
+}
+
+#[test]
+fn related_crawl_limits() {
+    let koto = Koto::new("related_crawl_limits");
+    let fixtures = koto.home().join("fixtures");
+    write_fixture(&fixtures, "api/token.json", &read_fixture("api/token.json"));
+    let artist = read_fixture("v1/artists/4tZwfgrHOc3mvqYlEYSvVN.json");
+    let id = |name: &str| format!("{:0>22}", name);
+    // a -> b, c ; b -> d, e ; c -> a, f ; d -> g
+    let related = [
+        ("a", vec!["b", "c"]),
+        ("b", vec!["d", "e"]),
+        ("c", vec!["a", "f"]),
+        ("d", vec!["g"]),
+        ("e", vec![]),
+        ("f", vec![]),
+        ("g", vec![]),
+    ];
+    let artist_json = |name: &str| {
+        let mut artist = artist.clone();
+        artist["id"] = id(name).into();
+        artist["name"] = name.into();
+        artist
+    };
+    for (name, related) in related.iter() {
+        write_fixture(
+            &fixtures,
+            &format!("v1/artists/{}.json", id(name)),
+            &artist_json(name),
+        );
+        let artists = related
+            .iter()
+            .map(|name| artist_json(name))
+            .collect::<Vec<_>>();
+        write_fixture(
+            &fixtures,
+            &format!("v1/artists/{}/related-artists.json", id(name)),
+            &serde_json::json!({ "artists": artists }),
+        );
+    }
+
+    let mock = MockApi::start_with(&fixtures);
+    let koto = koto.with_mock(&mock).init();
+    let crawl = |depth: &str, max_nodes: &str| {
+        let stdout = koto.stdout(&[
+            "related",
+            "--id",
+            &id("a"),
+            "--depth",
+            depth,
+            "--max-nodes",
+            max_nodes,
+            "--export",
+            "json",
+        ]);
+        let graph: serde_json::Value = serde_json::from_str(&stdout).expect("Invalid json");
+        let nodes = graph["nodes"]
+            .as_array()
+            .expect("No nodes")
+            .iter()
+            .map(|node| {
+                (
+                    node["name"].as_str().unwrap().to_string(),
+                    node["depth"].as_u64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let edges = graph["edges"].as_array().expect("No edges").len();
+        (nodes, edges)
+    };
+    let names = |nodes: &[(String, u64)]| {
+        nodes
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let (nodes, edges) = crawl("1", "50");
+    assert_eq!(names(&nodes), ["a", "b", "c"]);
+    assert_eq!(edges, 2);
+
+    let (nodes, edges) = crawl("2", "50");
+    assert_eq!(names(&nodes), ["a", "b", "c", "d", "e", "f"]);
+    assert!(nodes.iter().all(|(_, depth)| *depth <= 2), "{:?}", nodes);
+    // c -> a links back to the crawled artist, d -> g is beyond the depth
+    assert_eq!(edges, 6);
+
+    let (nodes, edges) = crawl("3", "4");
+    assert_eq!(names(&nodes), ["a", "b", "c", "d"]);
+    // b -> e, c -> f and d -> g are left out with their artists
+    assert_eq!(edges, 4);
+}